@@ -0,0 +1,153 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use winit::event::MouseButton;
+
+use crate::engine::{
+    BotSettings, EngineError, CONFIG, find_scenario,
+    headless::{simulate, InputDriver}, replay::ReplayEvent, scenario::Scenario, simulation::Simulation,
+};
+
+const HISTOGRAM_BINS: usize = 10;
+
+pub struct BotAimer {
+/*
+    Plays a scenario through the same input path as a human: it only produces mouse motion
+    and button events, the simulation decides what they hit.
+
+    Per tick it picks the target closest to the crosshair, waits reaction_ms after switching
+    targets, then either flicks towards it (limited by flick_speed) or, in tracking scenarios,
+    corrects a fixed share of the error per second (tracking_gain). Every movement gets noise.
+*/
+    settings: BotSettings,
+    tracking: bool,
+    automatic: bool,
+    rng: StdRng,
+    target_id: Option<u32>,
+    acquired_at: f32,
+    button_down: bool,
+}
+
+impl BotAimer {
+    pub fn new(settings: BotSettings, scenario: &Scenario, seed: u64) -> Self {
+        Self {
+            settings,
+            tracking: scenario.aiming_type.is_tracking(),
+            automatic: scenario.gun.automatic,
+            // Separate stream from the target rng, so the bot does not change where targets spawn
+            rng: StdRng::seed_from_u64(seed ^ 0xB07B07B0),
+            target_id: None,
+            acquired_at: 0.0,
+            button_down: false,
+        }
+    }
+
+    fn gaussian(&mut self) -> f32 {
+        // Box-Muller
+        let u1: f32 = self.rng.gen_range(f32::EPSILON..1.0);
+        let u2: f32 = self.rng.gen();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos()
+    }
+}
+
+impl InputDriver for BotAimer {
+    fn poll(&mut self, sim: &Simulation, events: &mut Vec<ReplayEvent>) {
+        let camera = &sim.camera;
+        let Some(target) = sim.targets.nearest_to_aim(camera) else {
+            return;
+        };
+        if self.target_id != Some(target.id) {
+            self.target_id = Some(target.id);
+            self.acquired_at = sim.time;
+        }
+        if sim.time - self.acquired_at < self.settings.reaction_ms / 1000.0 {
+            return;
+        }
+
+        // Angular error between the crosshair and the target centre
+        let to_target = camera.position.vec_to(&target.position);
+        let distance = to_target.length();
+        let dir = to_target.normalize();
+        let mut yaw_err = dir.x.atan2(dir.z) - camera.yaw;
+        yaw_err = (yaw_err + std::f32::consts::PI).rem_euclid(2.0 * std::f32::consts::PI) - std::f32::consts::PI;
+        let pitch_err = dir.y.clamp(-1.0, 1.0).asin() - camera.pitch;
+        let error = yaw_err.hypot(pitch_err);
+
+        let dt = sim.tick_duration;
+        let share = if self.tracking {
+            (self.settings.tracking_gain * dt).min(1.0)
+        } else if error > 0.0 {
+            (self.settings.flick_speed.to_radians() * dt / error).min(1.0)
+        } else {
+            0.0
+        };
+        let noise = self.settings.noise * error * share;
+        let step_yaw = yaw_err * share + noise * self.gaussian();
+        let step_pitch = pitch_err * share + noise * self.gaussian();
+
+        // Inverse of Camera::update_yaw_pitch, so the bot moves in mouse counts like a player
+        let counts_per_rad = 1.0 / (camera.sensitivity * 0.001);
+        if step_yaw != 0.0 || step_pitch != 0.0 {
+            events.push(ReplayEvent::MouseMotion { dx: step_yaw * counts_per_rad, dy: -step_pitch * counts_per_rad });
+        }
+
+        // Fire once the crosshair will be well inside the target after this step
+        let remaining = (yaw_err - step_yaw).hypot(pitch_err - step_pitch);
+        let on_target = remaining < (target.radius / distance.max(f32::EPSILON)).atan() * 0.8;
+
+        if self.button_down && (!self.automatic || !on_target) {
+            events.push(ReplayEvent::MouseReleased(MouseButton::Left));
+            self.button_down = false;
+        } else if !self.button_down && on_target {
+            events.push(ReplayEvent::MousePressed(MouseButton::Left));
+            self.button_down = true;
+        }
+    }
+}
+
+pub fn run_bot(scenario_names: &[&str], runs: Option<u32>, seed: u64) -> Result<(), EngineError> {
+/*
+    Plays every given scenario `runs` times headless (seeds seed, seed + 1, ..., wrapping at u64::MAX) and prints
+    the score distribution of each, so scenarios can be compared by difficulty.
+*/
+    let settings = CONFIG.bot;
+    let runs = runs.unwrap_or(settings.runs).max(1);
+    println!("\nBot: {:.0} ms reaction, {:.0}°/s flicks, tracking gain {:.1}, noise {:.2}",
+             settings.reaction_ms, settings.flick_speed, settings.tracking_gain, settings.noise);
+
+    for name in scenario_names {
+        let mut scenario = find_scenario(name)?;
+        let mut scores: Vec<f32> = (0..runs as u64).map(|i| {
+            let run_seed = seed.wrapping_add(i);
+            let mut bot = BotAimer::new(settings, &scenario, run_seed);
+            let mut sim = simulate(&mut scenario, run_seed, &mut bot);
+            sim.stats.score()
+        }).collect();
+        scores.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        print_distribution(&scenario.name, &scores);
+    }
+    Ok(())
+}
+
+fn print_distribution(name: &str, sorted: &[f32]) {
+    let n = sorted.len() as f32;
+    let mean = sorted.iter().sum::<f32>() / n;
+    let std_dev = (sorted.iter().map(|s| (s - mean).powi(2)).sum::<f32>() / n).sqrt();
+    let percentile = |p: f32| sorted[((sorted.len() - 1) as f32 * p).round() as usize];
+
+    println!("\n{} ({} runs)", name, sorted.len());
+    println!("Mean {:.0} ± {:.0} | Min {:.0} | P25 {:.0} | Median {:.0} | P75 {:.0} | Max {:.0}",
+             mean, std_dev, sorted[0], percentile(0.25), percentile(0.5), percentile(0.75), sorted[sorted.len() - 1]);
+
+    let (min, max) = (sorted[0], sorted[sorted.len() - 1]);
+    if max <= min {
+        return;
+    }
+    let mut bins = [0usize; HISTOGRAM_BINS];
+    for score in sorted {
+        let bin = ((score - min) / (max - min) * HISTOGRAM_BINS as f32) as usize;
+        bins[bin.min(HISTOGRAM_BINS - 1)] += 1;
+    }
+    let bin_width = (max - min) / HISTOGRAM_BINS as f32;
+    for (i, count) in bins.iter().enumerate() {
+        println!("{:>8.0} | {}", min + bin_width * i as f32, "#".repeat(*count));
+    }
+}
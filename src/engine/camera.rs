@@ -1,16 +1,18 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use winit::event_loop::ControlFlow;
 use std::f32::consts::{PI, FRAC_PI_2};
 
 use crate::engine::{Mat4x4, FPS_DIGIT_WIDTH};
 use crate::engine::core::{Vec3d, CONFIG};
 
+#[derive(Clone)]
 pub struct Camera {
     pub position: Vec3d,
     pub look_dir: Vec3d,
     pub view_matrix: Mat4x4,
     pub yaw: f32,
     pub pitch: f32, 
-    pub last_frame_time:Instant
+    pub sensitivity: f32,
 }
 
 impl Camera {
@@ -23,21 +25,21 @@ impl Camera {
             view_matrix: Mat4x4::point_at(&pos, &(pos + look_dir), &up).quick_inverse(),
             yaw:0.0,
             pitch:0.0,
-            last_frame_time:Instant::now()
+            sensitivity: CONFIG.input.sensitivity,
         }
     }
     pub fn update_yaw_pitch(&mut self, delta_x:f64, delta_y:f64) {
-        let sens = CONFIG.input.sensitivity;
+        let sens = self.sensitivity;
         self.yaw += delta_x as f32 * sens * 0.001;
         self.pitch -= delta_y as f32 * sens * 0.001;
     }
     pub fn update_look_dir(&mut self) {
         self.pitch = self.pitch.clamp(-FRAC_PI_2 + 0.01, FRAC_PI_2 - 0.01);
         self.yaw %= 2.0 * PI;
-        self.look_dir.x = self.pitch.cos() * self.yaw.sin();
-        self.look_dir.y = self.pitch.sin();
-        self.look_dir.z = self.pitch.cos() * self.yaw.cos();
-        self.look_dir = self.look_dir.normalize();
+        self.look_dir = Camera::look_dir_for(self.yaw, self.pitch);
+    }
+    pub fn look_dir_for(yaw: f32, pitch: f32) -> Vec3d {
+        Vec3d::new(pitch.cos() * yaw.sin(), pitch.sin(), pitch.cos() * yaw.cos()).normalize()
     }
     pub fn update_view_matrix(&mut self) {
         let up = Vec3d {x: 0.0, y: 1.0, z: 0.0};
@@ -49,26 +51,138 @@ pub struct FPS {
     pub total_frame_count: u32,
     pub fps_str: String,
     pub interval_frame_count: u32,
-    pub last_fps_display: Instant,
-    pub width_px: usize
+    pub last_fps_display: Duration,
+    pub width_px: usize,
+    pub render_time: Duration, // Time spent rendering the scene in the current interval
+    pub render_str: String, // Average scene render time per frame in ms, shown below the FPS
 }
 impl FPS {
-    pub fn init() -> Self {
+    pub fn init(now: Duration) -> Self {
         Self {
             total_frame_count: 0,
             fps_str: String::from("0"),
             interval_frame_count: 0,
-            last_fps_display: Instant::now(),
-            width_px: FPS_DIGIT_WIDTH
+            last_fps_display: now,
+            width_px: FPS_DIGIT_WIDTH,
+            render_time: Duration::ZERO,
+            render_str: String::from("0.0"),
         }
     }
-    pub fn update_str(&mut self, now: Instant) {
-        if now.duration_since(self.last_fps_display).as_secs_f32() >= 1.0 {
+    pub fn update_str(&mut self, now: Duration) {
+        // Saturating, a clock that is set back must not panic the counter
+        if now.saturating_sub(self.last_fps_display).as_secs_f32() >= 1.0 {
             self.fps_str = self.interval_frame_count.to_string();
-            self.last_fps_display = Instant::now();
+            let avg_render_ms = self.render_time.as_secs_f32() * 1000.0 / self.interval_frame_count.max(1) as f32;
+            self.render_str = format!("{:.1}", avg_render_ms);
+            self.render_time = Duration::ZERO;
+            self.last_fps_display = now;
             self.interval_frame_count = 0;
             self.width_px = FPS_DIGIT_WIDTH * self.fps_str.len();
         }
     }
 }
 
+pub struct FrameLimiter {
+/*
+    Caps the rendering frame rate (display.fps_cap). Instead of sleeping, the event loop
+    waits until the next frame is due, so input events keep being handled in between.
+*/
+    frame_time: Option<Duration>,
+    next_frame: Duration,
+}
+impl FrameLimiter {
+    pub fn new(fps_cap: u32, now: Duration) -> Self {
+        Self {
+            frame_time: (fps_cap > 0).then(|| Duration::from_secs_f64(1.0 / fps_cap as f64)),
+            next_frame: now,
+        }
+    }
+    pub fn ready(&mut self, now: Duration) -> bool {
+        let Some(frame_time) = self.frame_time else { return true };
+        if now < self.next_frame {
+            return false;
+        }
+        self.next_frame += frame_time;
+        if self.next_frame < now {
+            // Fell behind (e.g. a slow frame), do not try to catch up with a burst of frames
+            self.next_frame = now + frame_time;
+        }
+        true
+    }
+    pub fn control_flow(&self, now: Duration) -> ControlFlow {
+        match self.frame_time {
+            // winit wants a deadline as an Instant, this is the only place clock time is turned into one
+            Some(_) => ControlFlow::WaitUntil(Instant::now() + self.next_frame.saturating_sub(now)),
+            None => ControlFlow::Poll,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::clock::{Clock, ManualClock};
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn fps_counts_the_frames_of_each_second() {
+        let mut clock = ManualClock::new();
+        let mut fps = FPS::init(clock.now());
+        for _ in 0..120 {
+            clock.advance(MS * 10);
+            fps.interval_frame_count += 1;
+            fps.render_time += MS * 4;
+            fps.update_str(clock.now());
+        }
+        // The first second is shown after its 100th frame, the 20 frames after it are still counting
+        assert_eq!(fps.fps_str, "100");
+        assert_eq!(fps.render_str, "4.0");
+        assert_eq!(fps.interval_frame_count, 20);
+    }
+
+    #[test]
+    fn fps_survives_a_clock_set_back() {
+        let mut clock = ManualClock::new();
+        clock.set(Duration::from_secs(5));
+        let mut fps = FPS::init(clock.now());
+        clock.set(Duration::from_secs(2));
+        fps.interval_frame_count += 1;
+        fps.update_str(clock.now());
+        assert_eq!(fps.fps_str, "0");
+    }
+
+    #[test]
+    fn limiter_caps_the_frame_rate() {
+        let mut clock = ManualClock::new();
+        let mut limiter = FrameLimiter::new(100, clock.now());
+        let mut frames = 0;
+        for _ in 0..1000 {
+            if limiter.ready(clock.now()) {
+                frames += 1;
+            }
+            clock.advance(MS);
+        }
+        assert_eq!(frames, 100);
+    }
+
+    #[test]
+    fn limiter_does_not_catch_up_after_a_stall() {
+        let mut clock = ManualClock::new();
+        let mut limiter = FrameLimiter::new(100, clock.now());
+        assert!(limiter.ready(clock.now()));
+        clock.advance(Duration::from_millis(500));
+        assert!(limiter.ready(clock.now()));
+        assert!(!limiter.ready(clock.now()));
+        clock.advance(MS * 10);
+        assert!(limiter.ready(clock.now()));
+    }
+
+    #[test]
+    fn uncapped_limiter_is_always_ready() {
+        let clock = ManualClock::new();
+        let mut limiter = FrameLimiter::new(0, clock.now());
+        assert!((0..10).all(|_| limiter.ready(clock.now())));
+        assert_eq!(limiter.control_flow(clock.now()), ControlFlow::Poll);
+    }
+}
@@ -0,0 +1,130 @@
+use std::{
+    fs::OpenOptions,
+    io::ErrorKind,
+    path::PathBuf,
+    sync::mpsc::{self, SyncSender},
+    thread::JoinHandle,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use winit::keyboard::KeyCode;
+
+use crate::engine::{EngineError, CAPTURE_DIR, SCREENSHOT_DIR, save_argb_png};
+
+pub const SCREENSHOT_KEY: KeyCode = KeyCode::F12;
+const CAPTURE_QUEUE: usize = 16; // Frames waiting to be written before the run has to wait for the writer
+
+struct Frame {
+    path: PathBuf,
+    width: usize,
+    height: usize,
+    data: Vec<u32>,
+}
+impl Frame {
+    fn new(path: PathBuf, pixel_buffer: &[u32], width: usize, height: usize) -> Self {
+        // Pixels nothing was drawn on are 0, which would be transparent in the PNG
+        let data = pixel_buffer.iter().map(|p| p | 0xFF000000).collect();
+        Self { path, width, height, data }
+    }
+
+    fn save(&self) -> Result<(), EngineError> {
+        save_argb_png(&self.path, self.width, self.height, &self.data)
+    }
+}
+
+pub fn save_screenshot(pixel_buffer: &[u32], width: usize, height: usize) -> Result<PathBuf, EngineError> {
+/*
+    Writes the frame to screenshots/ on a separate thread, so taking one mid-run does not drop frames.
+    Returns the path right away, a failed write is reported when it happens.
+*/
+    std::fs::create_dir_all(&*SCREENSHOT_DIR)?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis());
+    let path = reserve_screenshot_path(timestamp)?;
+
+    let frame = Frame::new(path.clone(), pixel_buffer, width, height);
+    std::thread::spawn(move || {
+        if let Err(e) = frame.save() {
+            eprintln!("Warning: Failed to save screenshot {}: {}", frame.path.display(), e);
+        }
+    });
+    Ok(path)
+}
+
+fn reserve_screenshot_path(timestamp: u128) -> Result<PathBuf, EngineError> {
+/*
+    Two screenshots can share a millisecond. The file is created right here (the writer thread may not have
+    started yet), and a taken name gets a suffix: screenshot_<ms>.png, screenshot_<ms>_1.png, ...
+*/
+    for n in 0.. {
+        let name = if n == 0 { format!("screenshot_{}.png", timestamp) } else { format!("screenshot_{}_{}.png", timestamp, n) };
+        let path = SCREENSHOT_DIR.join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    unreachable!()
+}
+
+pub struct FrameCapture {
+/*
+    Saves every Nth frame of a run as frame_000001.png, frame_000002.png, ... in a folder of its own.
+    Encoding a PNG takes longer than a frame, so a background thread writes them in order.
+*/
+    every_nth: u32,
+    until_next: u32, // Frames to skip before the next one is saved
+    saved: u32,
+    dir: PathBuf,
+    sender: Option<SyncSender<Frame>>,
+    writer: Option<JoinHandle<()>>,
+}
+impl FrameCapture {
+    pub fn start(scenario_name: &str, every_nth: u32) -> Option<Self> {
+        // 0 disables capturing
+        if every_nth == 0 {
+            return None;
+        }
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let dir = CAPTURE_DIR.join(format!("{}_{}", scenario_name.to_lowercase().replace(' ', "_"), timestamp));
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            eprintln!("Warning: Failed to start frame capture: {}", e);
+            return None;
+        }
+
+        let (sender, receiver) = mpsc::sync_channel::<Frame>(CAPTURE_QUEUE);
+        let writer = std::thread::spawn(move || {
+            for frame in receiver {
+                if let Err(e) = frame.save() {
+                    eprintln!("Warning: Failed to save captured frame {}: {}", frame.path.display(), e);
+                }
+            }
+        });
+        Some(Self { every_nth, until_next: 0, saved: 0, dir, sender: Some(sender), writer: Some(writer) })
+    }
+
+    pub fn frame(&mut self, pixel_buffer: &[u32], width: usize, height: usize) {
+        if self.until_next > 0 {
+            self.until_next -= 1;
+            return;
+        }
+        self.until_next = self.every_nth - 1;
+        self.saved += 1;
+
+        let path = self.dir.join(format!("frame_{:06}.png", self.saved));
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(Frame::new(path, pixel_buffer, width, height));
+        }
+    }
+}
+impl Drop for FrameCapture {
+    fn drop(&mut self) {
+        // Closing the channel lets the writer finish the queued frames and stop
+        self.sender.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+        if self.saved > 0 {
+            println!("Captured {} frames to {}", self.saved, self.dir.display());
+        }
+    }
+}
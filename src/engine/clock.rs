@@ -0,0 +1,58 @@
+use std::time::{Duration, Instant};
+
+pub trait Clock {
+/*
+    The one source of time for everything timing dependent (frame deltas, FPS counter, frame cap).
+    Readings are the time since the clock started.
+*/
+    fn now(&self) -> Duration;
+}
+
+pub struct MonotonicClock {
+    // Real time. Unlike SystemTime this never jumps back when the wall clock is adjusted
+    start: Instant,
+}
+impl MonotonicClock {
+    pub fn new() -> Self {
+        Self { start: Instant::now() }
+    }
+}
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Clock for MonotonicClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+pub struct ManualClock {
+/*
+    Only moves when told to. Used where time is not real time, like the position in a replay
+    that can be paused, slowed down and seeked.
+*/
+    now: Duration,
+}
+impl ManualClock {
+    pub fn new() -> Self {
+        Self { now: Duration::ZERO }
+    }
+    pub fn advance(&mut self, by: Duration) {
+        self.now += by;
+    }
+    pub fn set(&mut self, to: Duration) {
+        self.now = to;
+    }
+}
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.now
+    }
+}
@@ -4,7 +4,7 @@ use once_cell::sync::Lazy;
 use std::path::{Path, PathBuf};
 use std::env;
 
-use crate::engine::Crosshair;
+use crate::engine::{Crosshair, EngineError};
 
 fn asset_path(relative_path: &str) -> PathBuf {
     let base = if cfg!(debug_assertions) {
@@ -23,15 +23,27 @@ const HIT_TARGET_REL: &str = if cfg!(debug_assertions) { "src/assets/sounds/hit_
 const POP_REL: &str = if cfg!(debug_assertions) { "src/assets/sounds/pop_sound.mp3" } else { "assets/sounds/pop_sound.mp3" };
 const CONFIG_PATH_REL: &str = if cfg!(debug_assertions) { "src/config.toml" } else { "config.toml" };
 const GUI_TXT_PATH_REL: &str = if cfg!(debug_assertions) { "src/assets/gui/gui.png" } else { "assets/gui/gui.png" };
+const HEATMAP_DIR_REL: &str = if cfg!(debug_assertions) { "src/heatmaps" } else { "heatmaps" };
+const REPLAY_DIR_REL: &str = if cfg!(debug_assertions) { "src/replays" } else { "replays" };
+const HISTORY_PATH_REL: &str = if cfg!(debug_assertions) { "src/history.jsonl" } else { "history.jsonl" };
+const TEXTURE_DIR_REL: &str = if cfg!(debug_assertions) { "src/assets/textures" } else { "assets/textures" };
+const SCREENSHOT_DIR_REL: &str = if cfg!(debug_assertions) { "src/screenshots" } else { "screenshots" };
+const CAPTURE_DIR_REL: &str = if cfg!(debug_assertions) { "src/captures" } else { "captures" };
 
 pub static HIT_TARGET: Lazy<PathBuf> = Lazy::new(|| asset_path(HIT_TARGET_REL));
 pub static POP: Lazy<PathBuf> = Lazy::new(|| asset_path(POP_REL));
 pub static CONFIG_PATH: Lazy<PathBuf> = Lazy::new(|| asset_path(CONFIG_PATH_REL));
 pub static GUI_TXT_PATH: Lazy<PathBuf> = Lazy::new(|| asset_path(GUI_TXT_PATH_REL));
+pub static HEATMAP_DIR: Lazy<PathBuf> = Lazy::new(|| asset_path(HEATMAP_DIR_REL));
+pub static REPLAY_DIR: Lazy<PathBuf> = Lazy::new(|| asset_path(REPLAY_DIR_REL));
+pub static HISTORY_PATH: Lazy<PathBuf> = Lazy::new(|| asset_path(HISTORY_PATH_REL));
+pub static TEXTURE_DIR: Lazy<PathBuf> = Lazy::new(|| asset_path(TEXTURE_DIR_REL));
+pub static SCREENSHOT_DIR: Lazy<PathBuf> = Lazy::new(|| asset_path(SCREENSHOT_DIR_REL));
+pub static CAPTURE_DIR: Lazy<PathBuf> = Lazy::new(|| asset_path(CAPTURE_DIR_REL));
 
 
 // Lazily load config at runtime, only once
-pub static CONFIG: Lazy<Config> = Lazy::new(|| load_config(&CONFIG_PATH));
+pub static CONFIG: Lazy<Config> = Lazy::new(|| load_config(&CONFIG_PATH).unwrap_or_else(|e| panic!("{}", e))); // main checks the file with load_config first
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
@@ -40,13 +52,32 @@ pub struct Config {
     pub input: Input,
     pub targets: Targets,
     pub environment: Environment,
-    pub crosshair:Crosshair
+    pub crosshair:Crosshair,
+    #[serde(default)]
+    pub run: RunSettings,
+    #[serde(default)]
+    pub bot: BotSettings,
+    #[serde(default)]
+    pub lighting: Lighting,
+    #[serde(default)]
+    pub capture: CaptureSettings,
 }
 
+fn default_supersampling() -> usize { 1 }
+fn default_render_scale() -> f32 { 1.0 }
+
 #[derive(Debug, Deserialize)]
 pub struct Display {
     pub width: usize,
     pub height: usize,
+    #[serde(default)]
+    pub fps_cap: u32, // 0 = unlimited
+    #[serde(default)]
+    pub render_threads: usize, // 0 = one per CPU core, 1 = single-threaded
+    #[serde(default = "default_supersampling")]
+    pub supersampling: usize, // Anti-aliasing: the scene is rendered at N x N samples per pixel and averaged
+    #[serde(default = "default_render_scale")]
+    pub render_scale: f32, // Fraction of the window resolution the scene is rendered at, then upscaled
 }
 
 #[derive(Debug, Deserialize)]
@@ -65,25 +96,114 @@ pub struct Input {
 #[derive(Debug, Deserialize)]
 pub struct Targets {
     pub color: [u8; 3],
-    pub sphere_detail: usize,
+    pub sphere_detail: usize, // Tessellation of the hit mesh, the render mesh depends on the size on screen
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Environment {
     pub scene_color: [u8;3],
-    pub light_direction: (f32, f32, f32)
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LightingMode {
+    Lit,
+    Unlit, // Every surface in its plain colour, for maximum target contrast
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Light {
+    Directional {
+        direction: (f32, f32, f32), // Points towards the light
+        color: [u8; 3],
+        intensity: f32,
+    },
+    Point {
+        position: (f32, f32, f32),
+        color: [u8; 3],
+        intensity: f32,
+        range: f32, // Distance at which the light has half its intensity, must be above 0
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Lighting {
+    pub mode: LightingMode,
+    pub ambient_color: [u8; 3],
+    pub ambient_intensity: f32,
+    pub lights: Vec<Light>,
+    pub target_specular: f32, // Strength of the highlights on targets, 0 = off
+    pub target_shininess: f32, // Higher is a smaller, sharper highlight
+}
+impl Default for Lighting {
+    fn default() -> Self {
+        Self {
+            mode: LightingMode::Lit,
+            ambient_color: [255, 255, 255],
+            ambient_intensity: 0.45,
+            lights: vec![Light::Directional { direction: (0.5, 2.0, -1.5), color: [255, 255, 255], intensity: 0.55 }],
+            target_specular: 0.0,
+            target_shininess: 32.0,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct RunSettings {
+    pub countdown_secs: f32, // 0 starts the run right away
+    pub start_on_first_hit: bool, // The run timer only starts counting with the first hit
+    pub tick_rate: u32, // Simulation ticks per second, independent of the frame rate
+}
+impl Default for RunSettings {
+    fn default() -> Self {
+        Self { countdown_secs: 3.0, start_on_first_hit: false, tick_rate: 240 }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct BotSettings {
+    pub reaction_ms: f32, // Delay before the bot starts moving towards a new target
+    pub flick_speed: f32, // Maximum crosshair speed in degrees per second
+    pub tracking_gain: f32, // How much of the remaining error is corrected per second while tracking
+    pub noise: f32, // Random error added to every movement, as a fraction of the movement
+    pub runs: u32, // Runs per scenario when calibrating
+}
+impl Default for BotSettings {
+    fn default() -> Self {
+        Self { reaction_ms: 200.0, flick_speed: 600.0, tracking_gain: 12.0, noise: 0.1, runs: 20 }
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct CaptureSettings {
+    pub every_nth_frame: u32, // Saves every Nth frame of a run as a numbered PNG, 0 = off
 }
 
 // CLI styling constants
 pub const BLUE: &str = "\x1b[94m";
 pub const RED: &str = "\x1b[31m";
+pub const GREEN: &str = "\x1b[92m";
 pub const RESET: &str = "\x1b[0m";
 
 pub const TIMER_DIGIT_WIDTH:usize = 30;
 pub const FPS_DIGIT_WIDTH: usize = 17;
 
-pub fn load_config(path: &Path) -> Config {
-    let config_str = fs::read_to_string(path).unwrap_or_else(|e| panic!("Failed to read config file at {:?}: {}", path, e));
-
-    toml::from_str::<Config>(&config_str).expect("Failed to parse config.toml")
-}
\ No newline at end of file
+pub fn load_config(path: &Path) -> Result<Config, EngineError> {
+    let config_str = fs::read_to_string(path).map_err(|e| EngineError::ConfigErr(format!("failed to read {:?}: {}", path, e)))?;
+
+    let config = toml::from_str::<Config>(&config_str).map_err(|e| EngineError::ConfigErr(e.to_string()))?;
+    // A range of 0 would divide by zero in the falloff and turn the lit colours into NaN
+    for (i, light) in config.lighting.lights.iter().enumerate() {
+        if let Light::Point { range, .. } = light {
+            if range.is_nan() || *range <= 0.0 {
+                return Err(EngineError::ConfigErr(format!("lighting.lights[{}]: point light range must be above 0, got {}", i, range)));
+            }
+        }
+    }
+    Ok(config)
+}
@@ -15,6 +15,12 @@ pub enum EngineError {
     #[error("Failed to decode PNG: {0}")]
     PngDecodeErr(#[from] png::DecodingError),
 
+    #[error("Failed to encode PNG: {0}")]
+    PngEncodeErr(#[from] png::EncodingError),
+
+    #[error("Failed to write file: {0}")]
+    IoErr(#[from] std::io::Error),
+
     #[error("ANSI compatibility check failed. Please run in a terminal.")]
     ColorCheckErr,
 
@@ -24,4 +30,22 @@ pub enum EngineError {
     #[error("Failed to load Sound Effects")]
     SFXErr,
 
+    #[error("Failed to serialize run history")]
+    HistoryErr,
+
+    #[error("Invalid or corrupted replay file")]
+    ReplayFormatErr,
+
+    #[error("Unknown scenario: {0}")]
+    UnknownScenarioErr(String),
+
+    #[error("Invalid input script (line {0}): {1}")]
+    ScriptErr(usize, String),
+
+    #[error("Invalid config.toml: {0}")]
+    ConfigErr(String),
+
+    #[error("Invalid value for {0}: {1}")]
+    ArgErr(&'static str, String),
+
 }
\ No newline at end of file
@@ -18,12 +18,14 @@ impl Mul<Vec3d> for Mat4x4 {
 impl Mul<Triangle> for Mat4x4 {
     type Output = Triangle;
     fn mul(self, tri: Triangle) -> Triangle {
+        // Normals stay in world space, they are only used for lighting
         Triangle {
             p: [
                 self.transform_vec(tri.p[0]),
                 self.transform_vec(tri.p[1]),
                 self.transform_vec(tri.p[2]),
-            ]
+            ],
+            n: tri.n,
         }
     }
 }
@@ -50,7 +52,7 @@ impl Mat4x4 {
     pub fn projection(width: f32, height:f32) -> Self {
         // Dynamically creates a projection matrix based on FOV, aspect ratio and far/near settings
         let fov_scale = 1.0 / (CONFIG.camera.fov * 0.5 * (PI / 180.0)).tan();
-        let aspect = height as f32 / width as f32;
+        let aspect = height / width;
         let near = CONFIG.camera.near; let far = CONFIG.camera.far;
         Self {
             m: [
@@ -5,6 +5,7 @@ use crate::engine::{Vec3d, core::Vec2d};
 #[derive(Clone, Copy, Debug)]
 pub struct Triangle {
     pub p: [Vec3d; 3],
+    pub n: [Vec3d; 3], // Vertex normals for shading, the face normal on all three for flat surfaces
 }
 impl Add<Vec3d> for Triangle {
     type Output = Triangle;
@@ -14,7 +15,8 @@ impl Add<Vec3d> for Triangle {
                 self.p[0] + vec,
                 self.p[1] + vec,
                 self.p[2] + vec,
-            ]
+            ],
+            n: self.n,
         }
     }
 }
@@ -26,22 +28,29 @@ impl Sub<Vec3d> for Triangle {
                 self.p[0] - vec,
                 self.p[1] - vec,
                 self.p[2] - vec,
-            ]
+            ],
+            n: self.n,
         }
     }
 }
 impl Triangle {
     pub fn new_origin() -> Self {
-        Self { p: [Vec3d {x: 0.0, y: 0.0, z: 0.0}; 3] }
+        Self { p: [Vec3d {x: 0.0, y: 0.0, z: 0.0}; 3], n: [Vec3d {x: 0.0, y: 0.0, z: 0.0}; 3] }
     }
     pub fn new(a: (f32, f32, f32), b: (f32, f32, f32), c: (f32, f32, f32)) -> Triangle {
         let p_a = Vec3d::new(a.0, a.1, a.2);
         let p_b = Vec3d::new(b.0, b.1, b.2);
         let p_c = Vec3d::new(c.0, c.1, c.2);
-        Self { p: [p_a, p_b, p_c] }
+        Self::flat([p_a, p_b, p_c])
     }
-    pub fn avg_z(&self) -> f32 {
-        (self.p[0].z + self.p[1].z + self.p[2].z) / 3.0
+    pub fn flat(p: [Vec3d; 3]) -> Triangle {
+        // Every vertex gets the face normal, so the triangle is shaded evenly
+        let mut tri = Self { p, n: [Vec3d::zero(); 3] };
+        tri.n = [tri.normal(); 3];
+        tri
+    }
+    pub fn with_normals(p: [Vec3d; 3], n: [Vec3d; 3]) -> Triangle {
+        Self { p, n }
     }
     pub fn translate(&mut self, xyz: Vec3d) {
         self.p[0] = self.p[0] + xyz; self.p[1] = self.p[1] + xyz; self.p[2] = self.p[2] + xyz;
@@ -55,15 +64,54 @@ impl Triangle {
 
 #[derive(Clone, Copy, Debug)]
 pub struct Triangle2d {
-    pub p: [Vec2d;3]
+    pub p: [Vec2d;3],
+    pub inv_z: [f32;3], // 1 / view space depth of each point, unlike z itself this is linear in screen space
+    pub shade: [Shade;3], // Light reaching each point, interpolated across the triangle (Gouraud shading)
+    pub uv_z: [Vec2d;3], // Texture coordinates divided by view space depth, which makes them linear in screen space
 }
 impl Triangle2d {
     pub fn new_origin() -> Self {
-        Self { p: [Vec2d {x: 0.0, y: 0.0}; 3] }
+        Self { p: [Vec2d {x: 0.0, y: 0.0}; 3], inv_z: [0.0; 3], shade: [Shade::UNLIT; 3], uv_z: [Vec2d {x: 0.0, y: 0.0}; 3] }
+    }
+}
+
+pub const SHADE_CHANNELS: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shade {
+/*
+    The light at one point, per RGB channel. The surface colour is multiplied by diffuse
+    (which includes the ambient light) and specular is added on top, so highlights show on any colour.
+*/
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+}
+impl Shade {
+    pub const UNLIT: Shade = Shade { diffuse: [1.0; 3], specular: [0.0; 3] };
+
+    pub fn lerp(&self, other: &Shade, t: f32) -> Shade {
+        let (a, b) = (self.channels(), other.channels());
+        Self::from_channels(std::array::from_fn(|c| a[c] + (b[c] - a[c]) * t))
+    }
+    pub fn channels(&self) -> [f32; SHADE_CHANNELS] {
+        let (d, s) = (self.diffuse, self.specular);
+        [d[0], d[1], d[2], s[0], s[1], s[2]]
+    }
+    pub fn from_channels(c: [f32; SHADE_CHANNELS]) -> Shade {
+        Shade { diffuse: [c[0], c[1], c[2]], specular: [c[3], c[4], c[5]] }
+    }
+    #[inline(always)]
+    pub fn apply(&self, color: u32) -> u32 {
+        let channel = |i: usize, shift: u32| {
+            let base = ((color >> shift) & 0xFF) as f32;
+            ((base * self.diffuse[i] + 255.0 * self.specular[i]) as u32).min(0xFF) << shift
+        };
+        (0xFF << 24) | channel(0, 16) | channel(1, 8) | channel(2, 0)
     }
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct TriToRaster {
-    pub tri: Triangle2d, pub color: u32, pub avg_z: f32,
+    pub tri: Triangle2d, pub color: u32, // Unlit colour, the rasterizer applies tri.shade
+    pub texture: Option<usize>, // Replaces color, index into the room's textures
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
 use std::ops::{Add, Sub, Mul, Div};
-use rand::prelude::ThreadRng;
+use rand::rngs::StdRng;
 use rand::Rng;
 
 #[derive(Clone, Copy, Debug)]
@@ -42,7 +42,7 @@ impl Vec3d {
     pub fn from_tuple(tuple:(f32,f32,f32)) -> Self {
         Self { x: tuple.0, y: tuple.1, z: tuple.2 }
     }
-    pub fn from_rng_range(a:Vec3d, b:Vec3d, rng:&mut ThreadRng) -> Self {
+    pub fn from_rng_range(a:Vec3d, b:Vec3d, rng:&mut StdRng) -> Self {
         Self {
             x: rng.gen_range(a.x.min(b.x)..=a.x.max(b.x)),
             y: rng.gen_range(a.y.min(b.y)..=a.y.max(b.y)),
@@ -57,7 +57,7 @@ impl Vec3d {
     }
     pub fn normalize(&self) -> Vec3d {
         let l = self.length();
-        Vec3d::new(self.x / l as f32, self.y / l as f32, self.z / l as f32)
+        Vec3d::new(self.x / l, self.y / l, self.z / l)
     }
     pub fn vec_to(&self, to: &Vec3d) -> Vec3d {
         Vec3d { x: to.x - self.x, y: to.y - self.y, z: to.z - self.z }
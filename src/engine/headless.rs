@@ -0,0 +1,275 @@
+use std::path::Path;
+use winit::{event::MouseButton, keyboard::KeyCode};
+
+use crate::engine::{EngineError, find_scenario, replay::ReplayEvent, scenario::Scenario, simulation::Simulation};
+
+// With start_on_first_hit a bot or script may never hit, this bounds the run in simulated seconds
+const MAX_SETUP_SECS: f32 = 60.0;
+
+pub trait InputDriver {
+/*
+    Programmatic replacement for winit events. Before every tick the driver sees the simulation
+    and may push input events, which are applied exactly like live input would be.
+*/
+    fn poll(&mut self, sim: &Simulation, events: &mut Vec<ReplayEvent>);
+}
+
+pub fn simulate(scenario: &mut Scenario, seed: u64, driver: &mut dyn InputDriver) -> Simulation {
+/*
+    Plays a whole run without window, renderer or audio. Time only moves in fixed ticks
+    (the simulation's virtual clock), so a run is fully determined by scenario, seed and input.
+*/
+    let mut sim = Simulation::new(scenario, seed, false);
+    let duration = scenario.duration_secs.as_secs_f32();
+    let mut events = Vec::new();
+
+    while sim.stats.elapsed < duration && sim.time < duration + MAX_SETUP_SECS {
+        driver.poll(&sim, &mut events);
+        for event in events.drain(..) {
+            sim.apply(scenario, event);
+        }
+        sim.frame(scenario, sim.tick_duration);
+    }
+    sim
+}
+
+pub struct ScriptedInput {
+/*
+    Input read from a text file, one event per line, sorted by time in seconds:
+
+        # time  event    arguments
+        0.00    move     120 -15      (mouse motion in raw counts, like winit's MouseMotion)
+        0.25    press    MouseLeft
+        0.27    release  MouseLeft
+        1.00    press    W            (W, A, S, D, Space, Shift)
+*/
+    events: Vec<(f32, ReplayEvent)>,
+    next: usize,
+}
+impl ScriptedInput {
+    pub fn empty() -> Self {
+        Self { events: Vec::new(), next: 0 }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, EngineError> {
+        let mut events = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let err = |msg: &str| EngineError::ScriptErr(i + 1, msg.to_string());
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let time: f32 = parts[0].parse().map_err(|_| err("expected a time in seconds"))?;
+
+            let event = match (parts.get(1).copied(), parts.get(2).copied()) {
+                (Some("move"), Some(dx)) => {
+                    let dx = dx.parse().map_err(|_| err("invalid dx"))?;
+                    let dy = parts.get(3).ok_or_else(|| err("missing dy"))?.parse().map_err(|_| err("invalid dy"))?;
+                    ReplayEvent::MouseMotion { dx, dy }
+                }
+                (Some(action @ ("press" | "release")), Some(name)) => {
+                    let pressed = action == "press";
+                    match (parse_button(name), parse_key(name)) {
+                        (Some(btn), _) if pressed => ReplayEvent::MousePressed(btn),
+                        (Some(btn), _) => ReplayEvent::MouseReleased(btn),
+                        (_, Some(key)) if pressed => ReplayEvent::KeyPressed(key),
+                        (_, Some(key)) => ReplayEvent::KeyReleased(key),
+                        _ => return Err(err("unknown key or button")),
+                    }
+                }
+                _ => return Err(err("expected 'move dx dy', 'press <input>' or 'release <input>'")),
+            };
+            events.push((time, event));
+        }
+        // Stable, so events at the same time keep their order from the file
+        events.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(Self { events, next: 0 })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, EngineError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+}
+impl InputDriver for ScriptedInput {
+    fn poll(&mut self, sim: &Simulation, events: &mut Vec<ReplayEvent>) {
+        while let Some((time, event)) = self.events.get(self.next) {
+            if *time > sim.time {
+                break;
+            }
+            events.push(*event);
+            self.next += 1;
+        }
+    }
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    match name.to_lowercase().as_str() {
+        "w" => Some(KeyCode::KeyW),
+        "a" => Some(KeyCode::KeyA),
+        "s" => Some(KeyCode::KeyS),
+        "d" => Some(KeyCode::KeyD),
+        "space" => Some(KeyCode::Space),
+        "shift" => Some(KeyCode::ShiftLeft),
+        _ => None,
+    }
+}
+
+fn parse_button(name: &str) -> Option<MouseButton> {
+    match name.to_lowercase().as_str() {
+        "mouseleft" => Some(MouseButton::Left),
+        "mouseright" => Some(MouseButton::Right),
+        "mousemiddle" => Some(MouseButton::Middle),
+        _ => None,
+    }
+}
+
+pub fn run_headless(scenario_name: &str, script: Option<&Path>, seed: u64) -> Result<(), EngineError> {
+    let mut scenario = find_scenario(scenario_name)?;
+    let mut input = match script {
+        Some(path) => ScriptedInput::load(path)?,
+        None => ScriptedInput::empty(),
+    };
+
+    let mut sim = simulate(&mut scenario, seed, &mut input);
+    let stats = &mut sim.stats;
+    let accuracy = stats.get_accuracy_p();
+    let score = stats.score();
+
+    println!("\n{} (seed {}, headless)", scenario.name, seed);
+    println!("Simulated: {:.2}s in {} ticks", sim.time, (sim.time / sim.tick_duration).round() as u64);
+    println!("Hits: {} | Shots: {} | Kills: {} | Accuracy: {:.2}% | Score: {:.0}",
+             stats.hits, stats.shots, stats.kills, accuracy, score);
+    if let Some(tracking) = stats.tracking.as_ref().and_then(|t| t.metrics()) {
+        tracking.print();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Vec3d;
+
+    const SCENARIO: &str = "Jumbo Tile Frenzy";
+
+    fn sweep_script() -> String {
+        // Sweeps the crosshair back and forth and clicks every 50 ms, enough to hit and miss a few targets
+        let mut script = String::new();
+        for i in 0..400 {
+            let t = i as f32 * 0.05;
+            let dx = if (i / 20) % 2 == 0 { 40 } else { -40 };
+            script.push_str(&format!("{t:.2} move {dx} {}\n{t:.2} press MouseLeft\n{:.2} release MouseLeft\n", (i % 7) - 3, t + 0.02));
+        }
+        script
+    }
+
+    fn aim_counts(sim: &Simulation, target: Vec3d) -> (f32, f32) {
+        // Mouse counts that turn the camera from where it looks now onto target (see Camera::update_yaw_pitch)
+        let dir = sim.camera.position.vec_to(&target).normalize();
+        let (yaw, pitch) = (dir.x.atan2(dir.z), dir.y.asin());
+        let per_count = sim.camera.sensitivity * 0.001;
+        ((yaw - sim.camera.yaw) / per_count, (sim.camera.pitch - pitch) / per_count)
+    }
+
+    fn shoot(sim: &mut Simulation, scenario: &mut Scenario, dx: f32, dy: f32) {
+        // Turns by (dx, dy) counts, then clicks once
+        sim.mouse_motion(dx as f64, dy as f64);
+        sim.mouse_pressed(MouseButton::Left);
+        sim.frame(scenario, sim.tick_duration);
+        sim.mouse_released(MouseButton::Left);
+        sim.frame(scenario, sim.tick_duration);
+    }
+
+    fn spawns(seed: u64) -> Vec<(f32, f32, f32)> {
+        let mut scenario = find_scenario(SCENARIO).unwrap();
+        let sim = Simulation::new(&mut scenario, seed, false);
+        sim.targets.vec.iter().map(|t| (t.position.x, t.position.y, t.position.z)).collect()
+    }
+
+    #[test]
+    fn same_seed_and_script_give_identical_stats() {
+        let run = || {
+            let mut scenario = find_scenario(SCENARIO).unwrap();
+            let mut input = ScriptedInput::parse(&sweep_script()).unwrap();
+            let mut sim = simulate(&mut scenario, 7, &mut input);
+            (sim.stats.hits, sim.stats.shots, sim.stats.kills, sim.stats.score(), sim.time, sim.camera.yaw, sim.camera.pitch)
+        };
+        let first = run();
+        assert!(first.1 > 0, "the script never fired");
+        assert_eq!(first, run());
+    }
+
+    #[test]
+    fn setup_before_the_first_hit_is_not_counted() {
+        let mut scenario = find_scenario(SCENARIO).unwrap();
+        let mut sim = Simulation::new(&mut scenario, 3, false);
+        sim.start_on_first_hit = true;
+        for _ in 0..100 {
+            sim.frame(&mut scenario, sim.tick_duration);
+        }
+
+        // Straight at the floor, no target is ever there
+        let (dx, dy) = aim_counts(&sim, sim.camera.position + Vec3d::new(0.0, -1.0, 0.01));
+        shoot(&mut sim, &mut scenario, dx, dy);
+        assert_eq!((sim.stats.hits, sim.stats.shots, sim.stats.elapsed), (0, 0, 0.0));
+        assert!(sim.stats.offsets.is_empty());
+
+        let target = sim.targets.vec[0].position;
+        let (dx, dy) = aim_counts(&sim, target);
+        shoot(&mut sim, &mut scenario, dx, dy);
+        assert_eq!((sim.stats.hits, sim.stats.shots, sim.stats.kills), (1, 1, 1));
+        // The tick of the hit and the one after it
+        assert_eq!(sim.stats.elapsed, 2.0 * sim.tick_duration);
+        assert_eq!(sim.stats.offsets.len(), 1);
+    }
+
+    #[test]
+    fn scripted_shots_hit_the_spawned_targets() {
+        // Flicks onto each of the three spawned targets, then one shot into the floor
+        let mut scenario = find_scenario(SCENARIO).unwrap();
+        let mut sim = Simulation::new(&mut scenario, 11, false);
+        let mut aims: Vec<Vec3d> = sim.targets.vec.iter().map(|t| t.position).collect();
+        assert_eq!(aims.len(), 3);
+        aims.push(sim.camera.position + Vec3d::new(0.0, -1.0, 0.01));
+
+        let mut script = String::new();
+        for (i, aim) in aims.into_iter().enumerate() {
+            let (dx, dy) = aim_counts(&sim, aim);
+            sim.camera.update_yaw_pitch(dx as f64, dy as f64);
+            let t = 0.5 * (i + 1) as f32;
+            script.push_str(&format!("{t:.2} move {dx} {dy}\n{t:.2} press MouseLeft\n{:.2} release MouseLeft\n", t + 0.05));
+        }
+
+        let mut scenario = find_scenario(SCENARIO).unwrap();
+        let mut input = ScriptedInput::parse(&script).unwrap();
+        let mut sim = simulate(&mut scenario, 11, &mut input);
+        assert_eq!((sim.stats.hits, sim.stats.shots, sim.stats.kills), (3, 4, 3));
+        assert_eq!(sim.stats.get_accuracy_p(), 75.0);
+        assert_eq!(sim.stats.score(), 225.0);
+        assert_eq!(sim.stats.offsets.len(), 4);
+        // Targets are respawned, so the count stays the same
+        assert_eq!(sim.targets.vec.len(), 3);
+    }
+
+    #[test]
+    fn different_seeds_give_different_spawns() {
+        assert_eq!(spawns(1), spawns(1));
+        assert_ne!(spawns(1), spawns(2));
+    }
+
+    #[test]
+    fn script_errors_report_the_line() {
+        let line = |text: &str| match ScriptedInput::parse(text) {
+            Err(EngineError::ScriptErr(line, _)) => line,
+            Err(e) => panic!("unexpected error: {e}"),
+            Ok(_) => panic!("parsed invalid script: {text:?}"),
+        };
+        assert_eq!(line("soon press W"), 1);
+        assert_eq!(line("# comment\n0.0 move 10"), 2);
+        assert_eq!(line("0.0 move 1 1\n0.1 move x 1"), 2);
+        assert_eq!(line("0.0 press Q"), 1);
+        assert_eq!(line("0.0 jump"), 1);
+        assert!(ScriptedInput::parse("0.0 move 1 -1 # look\n\n0.1 press mouseleft\n0.2 release Space").is_ok());
+    }
+}
@@ -10,6 +10,12 @@ pub struct InputState {
     pub mouse_buttons_just_pressed: HashSet<MouseButton>
 }
 
+impl Default for InputState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl InputState {
     pub fn new() -> Self {
         Self { 
@@ -66,7 +72,7 @@ impl InputState {
     }
     pub fn check_fullscreen(&self, window: &Window) {
         if self.keys_just_pressed.contains(&KeyCode::F11) {
-            toggle_fullscreen(&window);
+            toggle_fullscreen(window);
         }
     }
 }
@@ -5,6 +5,14 @@ pub mod rendering;
 pub mod runtime;
 pub mod input;
 pub mod camera;
+pub mod clock;
+pub mod simulation;
+pub mod replay;
+pub mod playback;
+pub mod state;
+pub mod headless;
+pub mod bot;
+pub mod capture;
 
 pub use core::*;
 pub use scenario::*;
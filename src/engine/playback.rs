@@ -0,0 +1,252 @@
+use softbuffer::{Context, Surface};
+use std::{num::NonZeroU32, path::Path, time::Duration};
+use winit::{
+    event::{ElementState, Event, WindowEvent},
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+use crate::engine::{
+    CONFIG, EngineError, GUI, GUI_TXT_PATH, Timer, Vec3d, camera::Camera, clock::{Clock, ManualClock}, draw_texture_optimized, find_scenario,
+    rendering::{draw_crosshair, draw_line, project_point, put_pixel, window, SceneRenderer},
+    replay::{FrameState, StateTrack, FLAG_HIT, FLAG_SHOT, STATE_EXTENSION},
+    scenario::{Target, TargetSettings},
+};
+
+const TRAIL_SECS: f32 = 1.0; // How far back the crosshair and target trails reach
+const SEEK_SECS: f32 = 2.0;
+const SPEEDS: [f32; 6] = [0.125, 0.25, 0.5, 1.0, 1.5, 2.0];
+const NORMAL_SPEED: usize = 3;
+const TRAIL_DISTANCE: f32 = 10.0; // The crosshair trail is drawn this far in front of the camera
+
+const CROSSHAIR_TRAIL_COLOR: u32 = 0xFFFFD400;
+const TARGET_TRAIL_COLOR: u32 = 0xFF00C8FF;
+const HIT_COLOR: u32 = 0xFF30FF30;
+const MISS_COLOR: u32 = 0xFFFF3030;
+
+struct Playback {
+    position: ManualClock, // Where in the run we are, moved by real time scaled by the speed
+    speed: usize, // Index into SPEEDS
+    paused: bool,
+}
+impl Playback {
+    fn time(&self) -> f32 {
+        self.position.now().as_secs_f32()
+    }
+    fn set_time(&mut self, track: &StateTrack, secs: f32) {
+        self.position.set(Duration::from_secs_f32(secs.clamp(0.0, track.duration())));
+    }
+    fn advance(&mut self, track: &StateTrack, delta_time: f32) {
+        if self.paused {
+            return;
+        }
+        self.position.advance(Duration::from_secs_f32(delta_time * SPEEDS[self.speed]));
+        if self.time() >= track.duration() {
+            self.set_time(track, track.duration());
+            self.paused = true;
+        }
+    }
+    fn seek(&mut self, track: &StateTrack, secs: f32) {
+        self.set_time(track, self.time() + secs);
+    }
+    fn step(&mut self, track: &StateTrack, frames: i64) {
+        // Stepping always pauses, otherwise the next redraw would run off again
+        self.paused = true;
+        let index = (track.frame_at(self.time()) as i64 + frames).clamp(0, track.frames.len() as i64 - 1);
+        self.set_time(track, track.frames[index as usize].time);
+    }
+}
+
+pub fn run_viewer(path: &Path, clock: &dyn Clock) -> Result<(), EngineError> {
+/*
+    Plays back the per-frame states of a run in the window. Nothing is simulated here,
+    the camera and targets are simply placed where they were in the recorded frame.
+*/
+    // Both the input replay and the state file can be passed, they share the file stem
+    let track = StateTrack::load(&path.with_extension(STATE_EXTENSION))?;
+    if track.frames.is_empty() {
+        return Err(EngineError::ReplayFormatErr);
+    }
+    let mut scenario = find_scenario(&track.scenario)?;
+
+    println!("\nViewing {} ({:.1}s)", track.scenario, track.duration());
+    println!("Space: pause/resume | Left/Right: seek {}s | Up/Down: speed | ,/.: step frame | Home: restart | Esc: quit", SEEK_SECS);
+
+    let (event_loop, window) = window::event_loop_setup()?;
+    let mut window_size = window.inner_size();
+
+    let context = Context::new(&window)?;
+    let mut surface = Surface::new(&context, &window)?;
+
+    let mut renderer = SceneRenderer::new(window_size.width as usize, window_size.height as usize, &scenario.room_style);
+    let mut pixel_buffer: Vec<u32> = vec![0; window_size.width as usize * window_size.height as usize];
+
+    let gui = match GUI::load_gui(GUI_TXT_PATH.to_str().unwrap()) {
+        Ok(g) => Some(g),
+        Err(e) => {
+            eprintln!("Warning: GUI failed to load: {}", e);
+            None
+        }
+    };
+
+    let mut camera = Camera::new(track.frames[0].position);
+    let mut targets: Vec<Target> = Vec::new();
+    let mut playback = Playback { position: ManualClock::new(), speed: NORMAL_SPEED, paused: false };
+    let mut timer = Timer::new();
+    let mut last_frame_time = clock.now();
+
+    let _ = event_loop.run(|event, window_target| {
+        window_target.set_control_flow(winit::event_loop::ControlFlow::Poll);
+
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => window_target.exit(),
+
+                WindowEvent::Resized(new_size) => {
+                    window_size = new_size;
+                    renderer.resize(window_size.width as usize, window_size.height as usize);
+                    pixel_buffer.resize(window_size.width as usize * window_size.height as usize, 0);
+                    surface
+                        .resize(
+                            NonZeroU32::new(window_size.width).unwrap(),
+                            NonZeroU32::new(window_size.height).unwrap(),
+                        )
+                        .unwrap();
+                }
+
+                WindowEvent::KeyboardInput { event: key_event, .. } if key_event.state == ElementState::Pressed => {
+                    if let PhysicalKey::Code(keycode) = key_event.physical_key {
+                        match keycode {
+                            KeyCode::Escape => window_target.exit(),
+                            KeyCode::Space => {
+                                // Resuming at the end starts over
+                                if playback.paused && playback.time() >= track.duration() {
+                                    playback.set_time(&track, 0.0);
+                                }
+                                playback.paused = !playback.paused;
+                            }
+                            KeyCode::ArrowLeft => playback.seek(&track, -SEEK_SECS),
+                            KeyCode::ArrowRight => playback.seek(&track, SEEK_SECS),
+                            KeyCode::ArrowUp | KeyCode::ArrowDown => {
+                                playback.speed = if keycode == KeyCode::ArrowUp {
+                                    (playback.speed + 1).min(SPEEDS.len() - 1)
+                                } else {
+                                    playback.speed.saturating_sub(1)
+                                };
+                                println!("Playback speed: {}x", SPEEDS[playback.speed]);
+                            }
+                            KeyCode::Comma => playback.step(&track, -1),
+                            KeyCode::Period => playback.step(&track, 1),
+                            KeyCode::Home => playback.set_time(&track, 0.0),
+                            _ => {}
+                        }
+                    }
+                }
+
+                WindowEvent::RedrawRequested => {
+                    let now = clock.now();
+                    playback.advance(&track, now.saturating_sub(last_frame_time).as_secs_f32());
+                    last_frame_time = now;
+
+                    let index = track.frame_at(playback.time());
+                    let frame = &track.frames[index];
+
+                    camera.position = frame.position;
+                    camera.yaw = frame.yaw;
+                    camera.pitch = frame.pitch;
+                    camera.update_look_dir();
+                    camera.update_view_matrix();
+                    sync_targets(&mut targets, frame, scenario.t_settings);
+
+                    // Recorded positions are exact, there is nothing to interpolate
+                    renderer.render(&mut pixel_buffer, &mut scenario.room, &mut targets, &camera, 1.0);
+                    draw_trails(&mut pixel_buffer, &renderer, &track, index, &camera);
+
+                    let width = window_size.width as usize;
+                    let height = window_size.height as usize;
+                    draw_crosshair(&mut pixel_buffer, CONFIG.crosshair, width, height);
+
+                    if let Some(gui) = &gui {
+                        draw_texture_optimized(&mut pixel_buffer, width, height, &gui.logo, 0, 0);
+
+                        // The timer counts up through the run instead of down
+                        timer.seconds = playback.time() as u64;
+                        timer.update_mins();
+                        timer.draw_timer(&mut pixel_buffer, width, height, gui);
+                    }
+                    let mut buffer = surface.buffer_mut().unwrap();
+                    buffer.copy_from_slice(&pixel_buffer);
+                    buffer.present().unwrap();
+                }
+                _ => (),
+            },
+            Event::AboutToWait => {
+                window.request_redraw();
+            }
+            _ => {}
+        }
+    });
+
+    Ok(())
+}
+
+fn sync_targets(targets: &mut Vec<Target>, frame: &FrameState, settings: TargetSettings) {
+/*
+    Targets are only meshes here: they get built the first frame they show up,
+    moved to their recorded position and dropped once they are gone (killed).
+*/
+    targets.retain(|t| frame.targets.iter().any(|s| s.id == t.id));
+    for state in &frame.targets {
+        match targets.iter_mut().find(|t| t.id == state.id) {
+            Some(target) => target.position = state.position,
+            None => {
+                let mut target = Target::new(settings, state.position, Vec3d::new(0.0, 0.0, 0.0));
+                target.id = state.id;
+                targets.push(target);
+            }
+        }
+    }
+}
+
+fn draw_trails(buf: &mut [u32], renderer: &SceneRenderer, track: &StateTrack, index: usize, camera: &Camera) {
+/*
+    Overlays the last TRAIL_SECS of the run onto the current frame:
+    1. the crosshair path, with shots marked green (hit) or red (miss)
+    2. the path of every target that is currently alive
+*/
+    let (width, height) = (renderer.width, renderer.height);
+    let current = &track.frames[index];
+    let trail = &track.frames[track.frame_at(current.time - TRAIL_SECS)..=index];
+    let project = |point: Vec3d| project_point(point, camera, &renderer.proj_matrix, width, height);
+
+    let mut prev: Option<(i32, i32)> = None;
+    for frame in trail {
+        let point = project(camera.position + frame.look_dir() * TRAIL_DISTANCE);
+        if let (Some((x0, y0)), Some((x1, y1))) = (prev, point) {
+            draw_line(buf, x0, y0, x1, y1, CROSSHAIR_TRAIL_COLOR, width, height);
+        }
+        if let Some((x, y)) = point.filter(|_| frame.flags & FLAG_SHOT != 0) {
+            let color = if frame.flags & FLAG_HIT != 0 { HIT_COLOR } else { MISS_COLOR };
+            draw_marker(buf, x, y, color, width, height);
+        }
+        prev = point;
+    }
+
+    for target in &current.targets {
+        let mut prev: Option<(i32, i32)> = None;
+        for frame in trail {
+            let point = frame.targets.iter().find(|t| t.id == target.id).and_then(|t| project(t.position));
+            if let (Some((x0, y0)), Some((x1, y1))) = (prev, point) {
+                draw_line(buf, x0, y0, x1, y1, TARGET_TRAIL_COLOR, width, height);
+            }
+            prev = point;
+        }
+    }
+}
+
+fn draw_marker(buf: &mut [u32], x: i32, y: i32, color: u32, width: usize, height: usize) {
+    for dy in -2..=2 {
+        for dx in -2..=2 {
+            put_pixel(buf, x + dx, y + dy, color, width, height);
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use std::io;
 use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
 use crate::engine::color::Colors;
+use crate::engine::{EngineError, History, HISTORY_PATH, RECENT_RUNS, ScenarioTrend, CategoryTrend, scenario_trends, category_trends, weakest_category};
 
 pub fn print_logo(colors: &Colors) {
     let logo = r#"
@@ -20,24 +22,21 @@ pub fn print_cli_select(n:usize, message:&str, colors: &Colors) {
 }
 
 pub fn print_categories(colors: &Colors) {
-    print_cli_select(0, "List all Scenarios\n", &colors);
+    print_cli_select(0, "List all Scenarios\n", colors);
     println!("{}AIMING TYPE CATEGORIES:{}", colors.blue, colors.reset);
-    print_cli_select(1, "Static Clicking", &colors);
-    print_cli_select(2, "Dynamic Clicking", &colors);
-    print_cli_select(3, "Reactive Tracking", &colors);
-    print_cli_select(4, "Precise Tracking", &colors);
-    print_cli_select(5, "Speed Switching", &colors);
-    print_cli_select(6, "Evasive Switching", &colors);
+    print_cli_select(1, "Static Clicking", colors);
+    print_cli_select(2, "Dynamic Clicking", colors);
+    print_cli_select(3, "Reactive Tracking", colors);
+    print_cli_select(4, "Precise Tracking", colors);
+    print_cli_select(5, "Speed Switching", colors);
+    print_cli_select(6, "Evasive Switching", colors);
     print!("\nSelect {}category{} by entering a number between '{}0{}' and '{}6{}': ",
            colors.blue, colors.reset, colors.blue, colors.reset, colors.blue, colors.reset
     );
-    match io::stdout().flush() {
-        Err(_) => {
-            println!("\nSelect {}category{} by entering a number between '{}0{}' and '{}6{}': ",
-                     colors.blue, colors.reset, colors.blue, colors.reset, colors.blue, colors.reset
-            );
-        },
-        _ => {}
+    if io::stdout().flush().is_err() {
+        println!("\nSelect {}category{} by entering a number between '{}0{}' and '{}6{}': ",
+                 colors.blue, colors.reset, colors.blue, colors.reset, colors.blue, colors.reset
+        );
     }
 }
 
@@ -68,13 +67,10 @@ pub fn get_category(colors: &Colors) -> usize {
 pub fn get_scenario_index(len: usize, colors: &Colors) -> usize {
     print!("\nSelect {}scenario{} by entering a number between '{}0{}' and '{}{}{}': ",
              colors.blue, colors.reset, colors.blue, colors.reset, colors.blue, len-1, colors.reset);
-    match io::stdout().flush() {
-        Err(_) => {
-            println!("\nPlease select a {}scenario{} by entering a number between '{}0{}' and '{}{}{}': ",
-                     colors.blue, colors.reset, colors.blue, colors.reset, colors.blue, len-1, colors.reset
-            );
-        },
-        _ => {}
+    if io::stdout().flush().is_err() {
+        println!("\nPlease select a {}scenario{} by entering a number between '{}0{}' and '{}{}{}': ",
+                 colors.blue, colors.reset, colors.blue, colors.reset, colors.blue, len-1, colors.reset
+        );
     }
 
     'scenario: loop {
@@ -101,23 +97,80 @@ pub fn get_scenario_index(len: usize, colors: &Colors) -> usize {
 
 pub fn play_again() -> bool {
     print!("Play again? (y/N): ");
-    match io::stdout().flush() {
-        Err(_) => { println!("Play again? (y/N): "); },
-        _ => {}
-    }
+    if io::stdout().flush().is_err() { println!("Play again? (y/N): "); }
     let mut input = String::new();
         io::stdin()
             .read_line(&mut input)
             .expect("Failed to read line");
 
     match input.trim().parse::<char>() {
-            Ok(char) => {
-                match char {
-                    'y' | 'Y' => {true}
-                    _ => {false},
-                }
-            },
+            Ok(char) => matches!(char, 'y' | 'Y'),
             Err(_) => {false}
     }
 
-}
\ No newline at end of file
+}
+
+pub fn run_stats_mode(plain: bool, colors: &Colors) -> Result<(), EngineError> {
+/*
+    Non-interactive mode ("stats" argument) that prints progress trends from the run history.
+    With "--plain" the output is a single tab separated table, one scenario per line, for use in scripts.
+*/
+    let history = History::load(&HISTORY_PATH)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let trends = scenario_trends(&history, now);
+    let categories = category_trends(&trends);
+
+    if plain {
+        print_trends_plain(&trends, &categories);
+    } else {
+        print_trends(&trends, &categories, colors);
+    }
+    Ok(())
+}
+
+fn fmt_change(change: Option<f32>) -> String {
+    change.map_or(String::from("-"), |c| format!("{:+.1}%", c))
+}
+
+fn print_trends(trends: &[ScenarioTrend], categories: &[CategoryTrend], colors: &Colors) {
+    println!("\n{}--- PROGRESS --- {}", colors.blue, colors.reset);
+    if trends.is_empty() {
+        println!("\nNo runs recorded yet. Play a scenario first!");
+        return;
+    }
+    for trend in trends {
+        let category = trend.aiming_type.map_or("Unknown", |t| t.name());
+        println!("\n{}{}{} ({})", colors.blue, trend.scenario.to_uppercase(), colors.reset, category);
+        println!("Runs: {}", trend.runs);
+        println!("Best: {:.0}  Worst: {:.0}", trend.best, trend.worst);
+        println!("Average: {:.0}  Last {} runs: {:.0}", trend.overall_avg, RECENT_RUNS, trend.rolling_avg);
+        println!("Last 7 days: {}  Last 30 days: {}", fmt_change(trend.change_7d), fmt_change(trend.change_30d));
+    }
+    if let Some(weakest) = weakest_category(categories) {
+        println!("\nWeakest category: {}{}{} (recent form at {:.0}% of its average)",
+                 colors.red, weakest.aiming_type.name(), colors.reset, weakest.relative_form * 100.0);
+    }
+    println!("\n{}--- END PROGRESS --- {}\n", colors.blue, colors.reset);
+}
+
+fn print_trends_plain(trends: &[ScenarioTrend], categories: &[CategoryTrend]) {
+    // One table, the category results are columns of every scenario in that category ("-" if it has none)
+    let weakest = weakest_category(categories).map(|c| c.aiming_type);
+    println!("scenario\taiming_type\truns\tbest\tworst\taverage\trolling_average\tchange_7d\tchange_30d\tcategory_form\tweakest_category");
+    for trend in trends {
+        let category = trend.aiming_type.and_then(|t| categories.iter().find(|c| c.aiming_type == t));
+        println!("{}\t{}\t{}\t{:.1}\t{:.1}\t{:.1}\t{:.1}\t{}\t{}\t{}\t{}",
+                 trend.scenario,
+                 trend.aiming_type.map_or("Unknown", |t| t.name()),
+                 trend.runs,
+                 trend.best,
+                 trend.worst,
+                 trend.overall_avg,
+                 trend.rolling_avg,
+                 trend.change_7d.map_or(String::from("-"), |c| format!("{:.1}", c)),
+                 trend.change_30d.map_or(String::from("-"), |c| format!("{:.1}", c)),
+                 category.map_or(String::from("-"), |c| format!("{:.3}", c.relative_form)),
+                 category.is_some() && trend.aiming_type == weakest,
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::engine::{Vec3d, Vec2d, Triangle, Triangle2d, TriToRaster, CONFIG};
+use crate::engine::{Vec3d, Vec2d, Triangle, Triangle2d, TriToRaster, core::Shade};
 
 pub fn vector_intersect_plane(
     plane_p: &Vec3d,
@@ -17,6 +17,15 @@ pub fn vector_intersect_plane(
     *line_start + line_to_intersect
 }
 
+// A triangle corner with everything that is interpolated along the clipped sides
+#[derive(Clone, Copy)]
+struct ClipVertex {
+    p: Vec2d,
+    inv_z: f32,
+    shade: Shade,
+    uv_z: Vec2d,
+}
+
 pub fn tri2d_clip_against_line(
     line_p: Vec2d,
     line_n: &mut Vec2d,
@@ -33,39 +42,21 @@ pub fn tri2d_clip_against_line(
     }
 
     // Temporary storage for inside/outside points
-    let mut inside_points: [Vec2d; 3] = [Vec2d::zero(); 3];
+    let mut inside_points: [ClipVertex; 3] = [ClipVertex { p: Vec2d::zero(), inv_z: 0.0, shade: Shade::UNLIT, uv_z: Vec2d::zero() }; 3];
     let mut n_inside_point = 0;
-    let mut outside_points: [Vec2d; 3] = [Vec2d::zero(); 3];
+    let mut outside_points: [ClipVertex; 3] = [ClipVertex { p: Vec2d::zero(), inv_z: 0.0, shade: Shade::UNLIT, uv_z: Vec2d::zero() }; 3];
     let mut n_outside_point = 0;
 
-    // Get signed distance of each point in triangle to line
-    let d0 = dist(in_tri.p[0], line_p, line_n);
-    let d1 = dist(in_tri.p[1], line_p, line_n);
-    let d2 = dist(in_tri.p[2], line_p, line_n);
-
-    // Classify points
-    if d0 >= 0.0 {
-        inside_points[n_inside_point] = in_tri.p[0];
-        n_inside_point += 1;
-    } else {
-        outside_points[n_outside_point] = in_tri.p[0];
-        n_outside_point += 1;
-    }
-
-    if d1 >= 0.0 {
-        inside_points[n_inside_point] = in_tri.p[1];
-        n_inside_point += 1;
-    } else {
-        outside_points[n_outside_point] = in_tri.p[1];
-        n_outside_point += 1;
-    }
-
-    if d2 >= 0.0 {
-        inside_points[n_inside_point] = in_tri.p[2];
-        n_inside_point += 1;
-    } else {
-        outside_points[n_outside_point] = in_tri.p[2];
-        n_outside_point += 1;
+    // Classify points by their signed distance to the line
+    for i in 0..3 {
+        let point = ClipVertex { p: in_tri.p[i], inv_z: in_tri.inv_z[i], shade: in_tri.shade[i], uv_z: in_tri.uv_z[i] };
+        if dist(in_tri.p[i], line_p, line_n) >= 0.0 {
+            inside_points[n_inside_point] = point;
+            n_inside_point += 1;
+        } else {
+            outside_points[n_outside_point] = point;
+            n_outside_point += 1;
+        }
     }
 
     // Now classify and construct output triangles
@@ -77,40 +68,55 @@ pub fn tri2d_clip_against_line(
 
     if n_inside_point == 3 {
         // All points inside
-        out_tri_array[0].p = in_tri.p;
+        out_tri_array[0] = *in_tri;
         return 1;
     }
 
-    // Helper: line intersection between two 2D points and a clipping line
+    // Helper: line intersection between two 2D points and a clipping line.
+    // 1/z, the shade and u/z, v/z are linear in screen space, so they are interpolated with the same factor.
     fn line_intersect(
         line_p: &Vec2d,
         line_n: &Vec2d,
-        p_start: &Vec2d,
-        p_end: &Vec2d,
-    ) -> Vec2d {
-        let line_dir = *p_end - *p_start;
-        let t = (line_n.dot(*line_p - *p_start)) / line_n.dot(line_dir);
-        *p_start + line_dir * t
+        start: &ClipVertex,
+        end: &ClipVertex,
+    ) -> ClipVertex {
+        let line_dir = end.p - start.p;
+        let t = (line_n.dot(*line_p - start.p)) / line_n.dot(line_dir);
+        ClipVertex {
+            p: start.p + line_dir * t,
+            inv_z: start.inv_z + (end.inv_z - start.inv_z) * t,
+            shade: start.shade.lerp(&end.shade, t),
+            uv_z: start.uv_z + (end.uv_z - start.uv_z) * t,
+        }
+    }
+    fn set_points(tri: &mut Triangle2d, points: [ClipVertex; 3]) {
+        for (i, point) in points.into_iter().enumerate() {
+            tri.p[i] = point.p;
+            tri.inv_z[i] = point.inv_z;
+            tri.shade[i] = point.shade;
+            tri.uv_z[i] = point.uv_z;
+        }
     }
 
     if n_inside_point == 1 && n_outside_point == 2 {
         // One inside point, form one new triangle
-        out_tri_array[0].p[0] = inside_points[0];
-        out_tri_array[0].p[1] = line_intersect(&line_p, &line_n, &inside_points[0], &outside_points[0]);
-        out_tri_array[0].p[2] = line_intersect(&line_p, &line_n, &inside_points[0], &outside_points[1]);
+        set_points(&mut out_tri_array[0], [
+            inside_points[0],
+            line_intersect(&line_p, &line_n, &inside_points[0], &outside_points[0]),
+            line_intersect(&line_p, &line_n, &inside_points[0], &outside_points[1]),
+        ]);
         return 1;
     }
 
     if n_inside_point == 2 && n_outside_point == 1 {
         // Two inside points, form two new triangles (quad split)
-        out_tri_array[0].p[0] = inside_points[0];
-        out_tri_array[0].p[1] = inside_points[1];
-        out_tri_array[0].p[2] = line_intersect(&line_p, &line_n, &inside_points[0], &outside_points[0]);
-
-        out_tri_array[1].p[0] = inside_points[1];
-        out_tri_array[1].p[1] = out_tri_array[0].p[2];
-        out_tri_array[1].p[2] = line_intersect(&line_p, &line_n, &inside_points[1], &outside_points[0]);
-
+        let shared = line_intersect(&line_p, &line_n, &inside_points[0], &outside_points[0]);
+        set_points(&mut out_tri_array[0], [inside_points[0], inside_points[1], shared]);
+        set_points(&mut out_tri_array[1], [
+            inside_points[1],
+            shared,
+            line_intersect(&line_p, &line_n, &inside_points[1], &outside_points[0]),
+        ]);
         return 2;
     }
 
@@ -124,7 +130,7 @@ pub fn triangle_clip_against_plane(
     out_tri_array: &mut [Triangle; 2],
 ) -> usize {
     // Make sure plane normal is indeed normal
-    let mut plane_n = plane_n.normalize();
+    let plane_n = plane_n.normalize();
 
     // Return signed shortest distance from point to plane, plane normal must be normalised
     fn dist(p: Vec3d, plane_p: Vec3d, plane_n: Vec3d) -> f32 {
@@ -132,50 +138,25 @@ pub fn triangle_clip_against_plane(
         // a = plane_n.x, b = plane_n.y, c = plane_n.z
         // d = - (plane_n dot plane_p)
         let d = -(plane_n.dot(plane_p));
-        return plane_n.x * p.x + plane_n.y * p.y + plane_n.z * p.z + d;
+        plane_n.x * p.x + plane_n.y * p.y + plane_n.z * p.z + d
     }
 
-    // Create two temporary storage arrays to classify points either side of plane
-    let mut inside_points: [&Vec3d; 3] = [&Vec3d {
-        x: 0.0,
-        y: 0.0,
-        z: 0.0,
-    }; 3];
+    // Create two temporary storage arrays to classify points either side of plane, each with its vertex normal
+    let mut inside_points: [(Vec3d, Vec3d); 3] = [(Vec3d::zero(), Vec3d::zero()); 3];
     let mut n_inside_point = 0;
-    let mut outside_points: [&Vec3d; 3] = [&Vec3d {
-        x: 0.0,
-        y: 0.0,
-        z: 0.0,
-    }; 3];
+    let mut outside_points: [(Vec3d, Vec3d); 3] = [(Vec3d::zero(), Vec3d::zero()); 3];
     let mut n_outside_point = 0;
 
     // Get signed distance of each point in triangle to plane
-    let d0 = dist(in_tri.p[0], plane_p, plane_n);
-    let d1 = dist(in_tri.p[1], plane_p, plane_n);
-    let d2 = dist(in_tri.p[2], plane_p, plane_n);
-
-    if d0 >= 0.0 {
-        inside_points[n_inside_point] = &in_tri.p[0];
-        n_inside_point += 1;
-    } else {
-        outside_points[n_outside_point] = &in_tri.p[0];
-        n_outside_point += 1;
-    }
-
-    if d1 >= 0.0 {
-        inside_points[n_inside_point] = &in_tri.p[1];
-        n_inside_point += 1;
-    } else {
-        outside_points[n_outside_point] = &in_tri.p[1];
-        n_outside_point += 1;
-    }
-
-    if d2 >= 0.0 {
-        inside_points[n_inside_point] = &in_tri.p[2];
-        n_inside_point += 1;
-    } else {
-        outside_points[n_outside_point] = &in_tri.p[2];
-        n_outside_point += 1;
+    for i in 0..3 {
+        let point = (in_tri.p[i], in_tri.n[i]);
+        if dist(in_tri.p[i], plane_p, plane_n) >= 0.0 {
+            inside_points[n_inside_point] = point;
+            n_inside_point += 1;
+        } else {
+            outside_points[n_outside_point] = point;
+            n_outside_point += 1;
+        }
     }
 
     if n_inside_point == 0 {
@@ -185,41 +166,37 @@ pub fn triangle_clip_against_plane(
 
     if n_inside_point == 3 {
         // all points are inside the plane
-        out_tri_array[0].p = in_tri.p;
+        out_tri_array[0] = *in_tri;
 
         return 1; // No clipping needed
     }
 
+    // Helper: the point where the side start -> end meets the plane (see vector_intersect_plane).
+    // The normal is blended with the same factor, so the shading stays continuous across the cut.
+    fn intersect(plane_p: &Vec3d, plane_n: &Vec3d, start: &(Vec3d, Vec3d), end: &(Vec3d, Vec3d)) -> (Vec3d, Vec3d) {
+        let point = vector_intersect_plane(plane_p, &mut plane_n.clone(), &start.0, &end.0);
+        let t = start.0.vec_to(&point).length() / start.0.vec_to(&end.0).length();
+        (point, start.1 + (end.1 - start.1) * t)
+    }
+    fn set_points(tri: &mut Triangle, points: [(Vec3d, Vec3d); 3]) {
+        for (i, (p, n)) in points.into_iter().enumerate() {
+            tri.p[i] = p;
+            tri.n[i] = n;
+        }
+    }
+
     if n_inside_point == 1 && n_outside_point == 2 {
         // Triangle should be clipped. As two points lie outside the plane, the triangle
         // simply becomes a smaller triangle.
 
-        // Copy appearance info to new triangle
-        out_tri_array[0].p = [Vec3d {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-        }; 3];
-
         // The inside point is valid, so keep that...
-        out_tri_array[0].p[0] = *inside_points[0];
-
         // but the two new points are at the locations where the original sides of the triangle (lines)
-        // intersect with the plane. We find these points by using a simple line-plane intersection
-        // formula (see vector_intersect_plane).
-
-        out_tri_array[0].p[1] = vector_intersect_plane(
-            &plane_p,
-            &mut plane_n.clone(),
+        // intersect with the plane.
+        set_points(&mut out_tri_array[0], [
             inside_points[0],
-            outside_points[0],
-        );
-        out_tri_array[0].p[2] = vector_intersect_plane(
-            &plane_p,
-            &mut plane_n.clone(),
-            inside_points[0],
-            outside_points[1],
-        );
+            intersect(&plane_p, &plane_n, &inside_points[0], &outside_points[0]),
+            intersect(&plane_p, &plane_n, &inside_points[0], &outside_points[1]),
+        ]);
 
         return 1; // Return the newly formed single triangle
     }
@@ -229,32 +206,18 @@ pub fn triangle_clip_against_plane(
         // the clipped triangle becomes a "quad". Fortunately, we can
         // represent a quad with two new triangles
 
-        // Copy appearance info to new triangles
-        // Copy appearance info to new triangle
-        out_tri_array[0].p = [Vec3d {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-        }; 3];
-        out_tri_array[1].p = [Vec3d {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-        }; 3];
-
-        // The inside point is valid, so keep that...
-        out_tri_array[0].p[0] = *inside_points[0];
-        out_tri_array[0].p[1] = *inside_points[1];
-        out_tri_array[0].p[2] =
-            vector_intersect_plane(&plane_p, &mut plane_n, inside_points[0], outside_points[0]);
+        // The inside points are valid, so keep those...
+        let shared = intersect(&plane_p, &plane_n, &inside_points[0], &outside_points[0]);
+        set_points(&mut out_tri_array[0], [inside_points[0], inside_points[1], shared]);
 
         // The second triangle is composed of one of he inside points, a
         // new point determined by the intersection of the other side of the
         // triangle and the plane, and the newly created point above
-        out_tri_array[1].p[0] = *inside_points[1];
-        out_tri_array[1].p[1] = out_tri_array[0].p[2];
-        out_tri_array[1].p[2] =
-            vector_intersect_plane(&plane_p, &mut plane_n, inside_points[1], outside_points[0]);
+        set_points(&mut out_tri_array[1], [
+            inside_points[1],
+            shared,
+            intersect(&plane_p, &plane_n, &inside_points[1], &outside_points[0]),
+        ]);
 
         return 2; // Return two newly formed triangles which form a quad
     }
@@ -262,8 +225,8 @@ pub fn triangle_clip_against_plane(
     0
 }
 
-pub fn tri_clip_xy(tri_to_raster:&TriToRaster, list_triangles:&mut Vec<TriToRaster>) {
-    list_triangles.push(tri_to_raster.clone());
+pub fn tri_clip_xy(tri_to_raster:&TriToRaster, list_triangles:&mut Vec<TriToRaster>, width: usize, height: usize) {
+    list_triangles.push(*tri_to_raster);
     let mut n_new_triangles = 1;
     for p in 0..4 {
         if list_triangles.is_empty() {
@@ -272,7 +235,7 @@ pub fn tri_clip_xy(tri_to_raster:&TriToRaster, list_triangles:&mut Vec<TriToRast
         let mut n_tris_to_add = 0;
         let mut clipped = [Triangle2d::new_origin(), Triangle2d::new_origin()];
         while n_new_triangles > 0 {
-            let mut test = list_triangles.pop().unwrap();
+            let test = list_triangles.pop().unwrap();
             n_new_triangles -= 1;
             match p {
                 0 => {
@@ -285,7 +248,7 @@ pub fn tri_clip_xy(tri_to_raster:&TriToRaster, list_triangles:&mut Vec<TriToRast
                             x: 0.0,
                             y: 1.0
                         },
-                        &mut test.tri,
+                        &test.tri,
                         &mut clipped,
                     );
                 }
@@ -293,13 +256,13 @@ pub fn tri_clip_xy(tri_to_raster:&TriToRaster, list_triangles:&mut Vec<TriToRast
                     n_tris_to_add = tri2d_clip_against_line(
                         Vec2d {
                             x: 0.0,
-                            y: CONFIG.display.height as f32 - 1.0,
+                            y: height as f32,
                         },
                         &mut Vec2d {
                             x: 0.0,
                             y: -1.0,
                         },
-                        &mut test.tri,
+                        &test.tri,
                         &mut clipped,
                     );
                 }
@@ -313,31 +276,30 @@ pub fn tri_clip_xy(tri_to_raster:&TriToRaster, list_triangles:&mut Vec<TriToRast
                             x: 1.0,
                             y: 0.0
                         },
-                        &mut test.tri,
+                        &test.tri,
                         &mut clipped,
                     );
                 }
                 3 => {
                     n_tris_to_add = tri2d_clip_against_line(
                         Vec2d {
-                            x: CONFIG.display.width as f32 - 1.0,
+                            x: width as f32,
                             y: 0.0
                         },
                         &mut Vec2d {
                             x: -1.0,
                             y: 0.0
                         },
-                        &mut test.tri,
+                        &test.tri,
                         &mut clipped,
                     );
                 }
                 _ => {}
             }
-            for w in 0..n_tris_to_add {
+            for tri in &clipped[..n_tris_to_add] {
                 list_triangles.push(TriToRaster {
-                    tri: clipped[w],
-                    color: tri_to_raster.color,
-                    avg_z: tri_to_raster.avg_z,
+                    tri: *tri,
+                    ..*tri_to_raster
                 });
             }
         }
@@ -1,13 +1,19 @@
-use std::io::BufReader;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
 use serde::Deserialize;
 use crate::engine::camera::FPS;
 use crate::engine::{EngineError, FPS_DIGIT_WIDTH, TIMER_DIGIT_WIDTH};
-use crate::engine::rasterizer::put_pixel;
+use crate::engine::rasterizer::{draw_line, put_pixel};
+
+// The countdown reuses the timer digits, scaled up so it reads from the centre of the screen
+const COUNTDOWN_SCALE: usize = 3;
+const OVERLAY_COLOR: u32 = 0xFFFFFFFF;
 
 pub struct GUI {
     pub logo: Texture,
     pub digits_timer: [Texture; 10],
     pub digits_fps: [Texture; 10],
+    pub digits_countdown: [Texture; 10],
     pub colon: Texture,
 }
 impl GUI {
@@ -37,9 +43,11 @@ impl GUI {
             texture.extract_region(136, 45, 153, 70),
             texture.extract_region(153, 45, 170, 70),
         ];
+        let digits_countdown = std::array::from_fn(|i| digits_timer[i].scaled(COUNTDOWN_SCALE));
         Ok(GUI {
             logo: texture.extract_region(0, 0, 222, 50),
             digits_timer,
+            digits_countdown,
             digits_fps,
             colon: texture.extract_region(0, 815, 18, 865)
         })
@@ -116,16 +124,56 @@ impl Texture {
         Self { width, height, data }
     }
 
+    // Nearest neighbour upscale, keeps the pixel look of the GUI sheet
+    pub fn scaled(&self, factor: usize) -> Self {
+        let width = self.width * factor;
+        let height = self.height * factor;
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let row = (y / factor) * self.width;
+            data.extend((0..width).map(|x| self.data[row + x / factor]));
+        }
+        Self { width, height, data }
+    }
+
     // Create a texture from raw ARGB data
     pub fn from_argb(width: usize, height: usize, data: Vec<u32>) -> Self {
         Self { width, height, data }
     }
+
+    pub fn save_to_png(&self, path: &Path) -> Result<(), EngineError> {
+        save_argb_png(path, self.width, self.height, &self.data)
+    }
+}
+
+/// Encode ARGB pixels (same layout as the pixel buffer) as an RGBA PNG file
+pub fn save_argb_png(path: &Path, width: usize, height: usize, data: &[u32]) -> Result<(), EngineError> {
+    let file = std::fs::File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+
+    let mut bytes = Vec::with_capacity(data.len() * 4);
+    for pixel in data {
+        bytes.push((pixel >> 16) as u8);
+        bytes.push((pixel >> 8) as u8);
+        bytes.push(*pixel as u8);
+        bytes.push((pixel >> 24) as u8);
+    }
+    writer.write_image_data(&bytes)?;
+    Ok(())
 }
 
 pub struct Timer {
     pub minutes: u64,
     pub seconds: u64
 }
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl Timer {
     pub fn new() -> Self {
         Self {minutes:0, seconds:0}
@@ -194,23 +242,21 @@ pub fn draw_texture_optimized(
     let tex_height = texture.height;
 
     // Calculate visible region (clipping)
-    let start_x = x.max(0);
-    let start_y = y.max(0);
     let end_x = (x + tex_width).min(buffer_width);
     let end_y = (y + tex_height).min(buffer_height);
 
-    if start_x >= end_x || start_y >= end_y {
+    if x >= end_x || y >= end_y {
         return; // Fully clipped
     }
 
     // Process row by row for cache efficiency
-    for dy in start_y..end_y {
-        let tex_y = (dy - y) as usize;
-        let tex_row_start = tex_y * texture.width as usize;
-        let dst_row_start = dy as usize * buffer_width;
+    for dy in y..end_y {
+        let tex_y = dy - y;
+        let tex_row_start = tex_y * texture.width;
+        let dst_row_start = dy * buffer_width;
 
-        for dx in start_x..end_x {
-            let tex_x = (dx - x) as usize;
+        for dx in x..end_x {
+            let tex_x = dx - x;
             let tex_idx = tex_row_start + tex_x;
 
             if tex_idx < texture.data.len() {
@@ -218,7 +264,7 @@ pub fn draw_texture_optimized(
                 let alpha = (src_pixel >> 24) & 0xFF;
 
                 if alpha > 0 {
-                    let dst_idx = dst_row_start + dx as usize;
+                    let dst_idx = dst_row_start + dx;
                     if dst_idx < buffer.len() {
                         if alpha == 255 {
                             // Fully opaque - direct copy (fastest path)
@@ -276,7 +322,39 @@ pub fn draw_fps(fps: &FPS, pixel_buffer: &mut [u32], width: usize, height: usize
     }
 }
 
-
+pub fn draw_render_stats(fps: &FPS, supersampling: usize, pixel_buffer: &mut [u32], width: usize, height: usize, texture: &[Texture]) {
+/*
+    Second line of the FPS overlay: the anti-aliasing factor and the average time the scene took to render,
+    e.g. "2x 4.5" for 2x2 supersampling at 4.5 ms per frame.
+    Only digits exist in the GUI texture, 'x' and '.' are drawn with lines.
+*/
+    let text = format!("{}x {}", supersampling, fps.render_str);
+    let y = texture[0].height + 4;
+    let glyph_width = |c: char| if c.is_ascii_digit() || c == 'x' { FPS_DIGIT_WIDTH } else { FPS_DIGIT_WIDTH / 2 };
+    let mut x = width.saturating_sub(text.chars().map(glyph_width).sum());
+
+    for c in text.chars() {
+        match c {
+            '0'..='9' => draw_texture_optimized(pixel_buffer, width, height, &texture[c as usize - '0' as usize], x, y),
+            'x' => {
+                let (x0, y0) = (x as i32 + 4, y as i32 + 10);
+                let (x1, y1) = (x as i32 + FPS_DIGIT_WIDTH as i32 - 5, y as i32 + texture[0].height as i32 - 4);
+                draw_line(pixel_buffer, x0, y0, x1, y1, OVERLAY_COLOR, width, height);
+                draw_line(pixel_buffer, x0, y1, x1, y0, OVERLAY_COLOR, width, height);
+            }
+            '.' => {
+                let bottom = (y + texture[0].height) as i32 - 4;
+                for dy in 0..3 {
+                    for dx in 0..3 {
+                        put_pixel(pixel_buffer, x as i32 + 2 + dx, bottom - dy, OVERLAY_COLOR, width, height);
+                    }
+                }
+            }
+            _ => {}
+        }
+        x += glyph_width(c);
+    }
+}
 
 #[derive(Debug, Clone, Copy, Deserialize)]
 pub struct Crosshair {
@@ -310,4 +388,17 @@ pub fn draw_crosshair(
             put_pixel(buf, center_x + dx, center_y - dy, crosshair.color, width, height);
         }
     }
-}
\ No newline at end of file
+}
+
+pub fn draw_countdown(seconds: u32, pixel_buffer: &mut [u32], width: usize, height: usize, texture: &[Texture]) {
+    // Centred horizontally, just above the crosshair
+    let digits = seconds.to_string();
+    let digit_width = texture[0].width;
+    let x_start = (width / 2).saturating_sub(digits.len() * digit_width / 2);
+    let y = (height / 2).saturating_sub(texture[0].height + 40);
+
+    for (i, byte) in digits.bytes().enumerate() {
+        let digit = (byte - b'0') as usize;
+        draw_texture_optimized(pixel_buffer, width, height, &texture[digit], x_start + i * digit_width, y);
+    }
+}
@@ -5,6 +5,7 @@ use std::f32::consts::PI;
 
 use crate::engine::camera::{Camera};
 use crate::engine::core::{CONFIG, Mesh, Triangle, Vec3d};
+use crate::engine::scenario::Target;
 
 /*  This turns the view cone specified as FOV into a scaling factor for
 projection, which will make sure that, as the FOV increases, the
@@ -20,17 +21,20 @@ pub fn ow_to_engine_fov(fov_h_deg: f32) -> f32 {
     fov_v_rad.to_degrees()
 }
 
-pub fn sigmoid(x: f32) -> f32 {
-    1.0 / (1.0 + (-1.5 * (x - 0.5)).exp())
-}
-
 pub fn load_from_obj(path: &str) -> Result<Mesh, std::io::Error> {
     let file = fs::File::open(path)?;
     let reader = BufReader::new(file);
     let mut verts: Vec<Vec3d> = Vec::new();
+    let mut normals: Vec<Option<Vec3d>> = Vec::new(); // None for normals that failed to parse
     let mut tris: Vec<Triangle> = Vec::new();
 
-    for line in reader.lines() {
+    let invalid = |line: usize, what: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{} line {}: {}", path, line, what));
+    let vec3 = |parts: &[&str]| -> Option<Vec3d> {
+        let c = parts.get(1..4)?;
+        Some(Vec3d::new(c[0].parse().ok()?, c[1].parse().ok()?, c[2].parse().ok()?))
+    };
+
+    for (i, line) in reader.lines().enumerate() {
         let line = line?;
         let parts: Vec<&str> = line.split_whitespace().collect();
 
@@ -41,24 +45,31 @@ pub fn load_from_obj(path: &str) -> Result<Mesh, std::io::Error> {
         match parts[0] {
             "v" => {
                 // vertex
-                let x: f32 = parts[1].parse().unwrap();
-                let y: f32 = parts[2].parse().unwrap();
-                let z: f32 = parts[3].parse().unwrap();
-                verts.push(Vec3d::new(x, y, z));
+                verts.push(vec3(&parts).ok_or_else(|| invalid(i + 1, "expected 'v x y z'"))?);
+            }
+            "vn" => {
+                // vertex normal, a broken one only makes the faces using it flat
+                normals.push(vec3(&parts).map(|n| n.normalize()));
             }
             "f" => {
-                // face (triangles only for now)
-                let idx1: usize = parts[1].split('/').next().unwrap().parse().unwrap();
-                let idx2: usize = parts[2].split('/').next().unwrap().parse().unwrap();
-                let idx3: usize = parts[3].split('/').next().unwrap().parse().unwrap();
-
-                tris.push(Triangle {
-                    p: [
-                        verts[idx1 - 1], // .obj indices start at 1
-                        verts[idx2 - 1],
-                        verts[idx3 - 1],
-                    ],
-                });
+                // face (triangles only for now), each corner is v, v/vt, v//vn or v/vt/vn. .obj indices start at 1
+                let index = |i: Option<&str>| i.and_then(|i| i.parse::<usize>().ok()).and_then(|i| i.checked_sub(1));
+                let corner = |part: &str| -> Option<(Vec3d, Option<Vec3d>)> {
+                    let mut indices = part.split('/');
+                    let v = *verts.get(index(indices.next())?)?;
+                    let vn = index(indices.nth(1)).and_then(|vn| normals.get(vn).copied().flatten());
+                    Some((v, vn))
+                };
+                let corners = parts.get(1..4)
+                    .and_then(|c| Some([corner(c[0])?, corner(c[1])?, corner(c[2])?]))
+                    .ok_or_else(|| invalid(i + 1, "expected a face of three existing vertices"))?;
+                let p = corners.map(|(v, _)| v);
+
+                // Faces without normals for every corner are shaded flat
+                match corners.map(|(_, vn)| vn) {
+                    [Some(n0), Some(n1), Some(n2)] => tris.push(Triangle::with_normals(p, [n0, n1, n2])),
+                    _ => tris.push(Triangle::flat(p)),
+                }
             }
             _ => {} // ignore other lines
         }
@@ -68,7 +79,32 @@ pub fn load_from_obj(path: &str) -> Result<Mesh, std::io::Error> {
 }
 
 
+pub fn target_aimed_at(target: &Target, camera: &Camera) -> Option<Vec3d> {
+/*
+    Returns the world space point the crosshair ray meets the target at, if it does.
+    Triangles facing away from the camera are skipped, the ray always enters through a front face first.
+    Of the front faces that are hit the nearest one is kept, mesh order says nothing about depth.
+*/
+    let mut nearest: Option<f32> = None;
+    for tri in &target.tris {
+        let tri_world = *tri + target.position;
+        if tri_world.normal().dot(camera.position.vec_to(&tri_world.p[0])) > 0.0 {
+            continue;
+        }
+        if let Some(t) = ray_triangle_distance(camera, &tri_world) {
+            if nearest.is_none_or(|n| t < n) {
+                nearest = Some(t);
+            }
+        }
+    }
+    nearest.map(|t| camera.position + camera.look_dir * t)
+}
+
 pub fn ray_intersects_triangle(camera:&Camera, triangle: &Triangle) -> bool {
+    ray_triangle_distance(camera, triangle).is_some()
+}
+
+pub fn ray_triangle_distance(camera:&Camera, triangle: &Triangle) -> Option<f32> {
     // Implementation of the Möller–Trumbore intersection algorithm
     // src: https://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm
 
@@ -86,7 +122,7 @@ pub fn ray_intersects_triangle(camera:&Camera, triangle: &Triangle) -> bool {
     */
 
     if det > -f32::EPSILON && det < f32::EPSILON {
-        return false;
+        return None;
     } 
 
     let inv_det: f32 = 1.0 / det;
@@ -95,39 +131,36 @@ pub fn ray_intersects_triangle(camera:&Camera, triangle: &Triangle) -> bool {
 
     if (u < 0.0 && u.abs() > f32::EPSILON) || (u > 1.0 && (u - 1.0).abs() > f32::EPSILON) {
         // checking if  0 ≤ u ≤ 1
-        return false;
+        return None;
     }
 
     let s_cross_e1: Vec3d = s.cross(edge1);
     let v: f32 = inv_det * camera.look_dir.dot(s_cross_e1);
 
     if (v < 0.0 && v.abs() > f32::EPSILON) || (u + v > 1.0 && (u + v - 1.0).abs() > f32::EPSILON) {
-        return false;
+        return None;
     }
 
     /*
-    At this point we know that the ray points at the triangle
-    The following will calculate the exact point that the ray intersects the triangle
-    We only care about the boolean though
-
-    let t = inv_det * dot_product(&e2, &s_cross_e1);
-
-    if t > f32::EPSILON { // ray intersection
-        let intersection_point:Vec3d = vec_add(ray_origin, &vec_multiply(ray_vec, t));
-        return Some(intersection_point);
+    At this point we know that the line through the ray meets the triangle.
+    The distance along the ray gives us the exact point the ray intersects the triangle,
+    which is used to record where on the target a shot landed:
+    intersection_point = camera.position + camera.look_dir * t
+    Intersections at or behind the camera are not hits.
+    */
+    let t: f32 = inv_det * edge2.dot(s_cross_e1);
+    if t > f32::EPSILON {
+        Some(t)
+    } else {
+        None
     }
-    else { // This means that there is a line intersection but not a ray intersection.
-        return None;
-    }*/
-
-    true
 }
 
 pub fn dyn_clamp_pos(pos:f32, vel:f32, n1:f32, n2:f32) -> (f32,f32) {
         if n1 < n2 && (pos < n1 || pos > n2) {
-            return (pos.clamp(n1, n2), vel * -1.0);
+            return (pos.clamp(n1, n2), -vel);
         } else if n2 < n1 && (pos < n2 || pos > n1) {
-            return (pos.clamp(n2, n1), vel * -1.0);
+            return (pos.clamp(n2, n1), -vel);
         } else if n2 == n1 {
             return (n1, vel);
         }
@@ -0,0 +1,48 @@
+use crate::engine::core::{CONFIG, Light, LightingMode, Shade, Vec3d};
+
+pub fn shade_vertex(position: Vec3d, normal: Vec3d, eye: Vec3d, specular: f32) -> Shade {
+/*
+    The light reaching one vertex from the [lighting] section in config.toml:
+    ambient light, plus Lambert diffuse and (with specular > 0) Blinn-Phong highlights from every light.
+    position, normal and eye (the camera position) are in world space.
+*/
+    let lighting = &CONFIG.lighting;
+    if lighting.mode == LightingMode::Unlit {
+        return Shade::UNLIT;
+    }
+    let rgb = |color: [u8; 3]| color.map(|c| c as f32 / 255.0);
+    let mut shade = Shade {
+        diffuse: rgb(lighting.ambient_color).map(|c| c * lighting.ambient_intensity),
+        specular: [0.0; 3],
+    };
+
+    // Normals blended by clipping are no longer unit length
+    let normal = normal.normalize();
+    let to_eye = position.vec_to(&eye).normalize();
+    for light in &lighting.lights {
+        let (to_light, color, intensity) = match *light {
+            Light::Directional { direction, color, intensity } => (Vec3d::from_tuple(direction).normalize(), color, intensity),
+            Light::Point { position: light_position, color, intensity, range } => {
+                let to_light = position.vec_to(&Vec3d::from_tuple(light_position));
+                let falloff = 1.0 / (1.0 + (to_light.length() / range).powi(2));
+                (to_light.normalize(), color, intensity * falloff)
+            }
+        };
+        let lambert = normal.dot(to_light);
+        if lambert <= 0.0 {
+            continue; // Facing away from this light
+        }
+        let color = rgb(color);
+        let highlight = if specular > 0.0 {
+            let half = (to_light + to_eye).normalize();
+            specular * normal.dot(half).max(0.0).powf(lighting.target_shininess)
+        } else {
+            0.0
+        };
+        for ((diffuse, specular), c) in shade.diffuse.iter_mut().zip(&mut shade.specular).zip(color) {
+            *diffuse += c * intensity * lambert;
+            *specular += c * intensity * highlight;
+        }
+    }
+    shade
+}
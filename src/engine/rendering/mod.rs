@@ -6,6 +6,9 @@ pub mod rasterizer;
 pub mod gui;
 pub mod cli;
 pub mod color;
+pub mod renderer;
+pub mod lighting;
+pub mod room_texture;
 
 pub use helpers::*;
 pub use projection::*;
@@ -13,3 +16,6 @@ pub use window::*;
 pub use clipping::*;
 pub use rasterizer::*;
 pub use gui::*;
+pub use renderer::*;
+pub use lighting::*;
+pub use room_texture::*;
@@ -1,23 +1,27 @@
 use crate::engine::{
-    camera::Camera, core::{CONFIG, Mat4x4, Mesh, TriToRaster, Triangle2d, Vec3d, }, rendering::{ray_intersects_triangle, sigmoid, tri_clip_z}, scenario::Target
+    camera::Camera, core::{CONFIG, Mat4x4, Mesh, TriToRaster, Triangle, Triangle2d, Vec2d, Vec3d, }, rendering::{shade_vertex, tri_clip_z, RoomTextures}, scenario::Surface
 };
 
 
-pub fn target_proj_loop(target: &mut Target, tri_vec: &mut Vec<TriToRaster>, camera: &Camera, gun_shot:bool, proj_matrix: &Mat4x4) -> bool {
+pub fn target_proj_loop(tris: &[Triangle], position: Vec3d, tri_vec: &mut Vec<TriToRaster>, camera: &Camera, proj_matrix: &Mat4x4, width: usize, height: usize) {
 /*  
-    This function performs both rendering (transformation, shading, projection, clipping),
-    as well as hit detection on all triangles of a target.
-    It returns True only if the player shot their gun and actually hit the target. 
+    This function performs rendering preperation (transformation, shading, projection, clipping) on all triangles of a target.
+    Hit detection is done by the simulation beforehand (see target_aimed_at).
+    tris is the target's render mesh, which may be more or less detailed than its hit mesh (see TargetMeshes).
+    position is where the target is drawn, which lies between two simulation ticks (see Target::render_position).
+    width and height are the size of the buffer that is rendered into, not the configured window size.
 */
-    let mut hit = false;
+
+    // Lighting is done in world space, clipped points are taken back there with the inverse view matrix
+    let to_world = camera.view_matrix.quick_inverse();
 
     //It utilizes the following procedure on each loop iteration:
-    for tri in &mut target.tris {
+    for tri in tris {
 
     /*
         1. transform the triangles' position (in case of movement) 
         [we do this because it's faster than separately looping through each triangle again]
-    */  let tri_world = *tri + target.position;
+    */  let tri_world = *tri + position;
         
 
     /* 
@@ -29,8 +33,8 @@ pub fn target_proj_loop(target: &mut Target, tri_vec: &mut Vec<TriToRaster>, cam
         }
 
     /*
-        3. Apply colorized shading utilizing the normal of the triangle
-    */  let color = color_triangle(normal, CONFIG.targets.color);
+        3. The lighting is computed per vertex from the vertex normals (see step 6)
+    */  let color = rgb_color(CONFIG.targets.color);
 
 
     /* 
@@ -45,39 +49,34 @@ pub fn target_proj_loop(target: &mut Target, tri_vec: &mut Vec<TriToRaster>, cam
 
 
     /*
-        6. Converts Triangles from camera space to Projection (Screen) space, meaning we can omit z completely and 
-        turn the Vec3d's into Vec2d's (to safe some performance)
+        6. Converts Triangles from camera space to Projection (Screen) space. Only 1/z is kept per point,
+        for the depth test in the rasterizer, along with its light which the rasterizer blends across the triangle
     */  let mut tri_projected = Triangle2d::new_origin();
-        for n in 0..n_clipped {
+        for clipped_tri in &clipped[..n_clipped] {
             for i in 0..3 {
-                tri_projected.p[i] = proj_matrix.project_vec(clipped[n].p[i]);
-                tri_projected.p[i].x = (1.0 - tri_projected.p[i].x) * 0.5 * CONFIG.display.width as f32;
-                tri_projected.p[i].y = (1.0 - tri_projected.p[i].y) * 0.5 * CONFIG.display.height as f32;
+                tri_projected.p[i] = proj_matrix.project_vec(clipped_tri.p[i]);
+                tri_projected.p[i].x = (1.0 - tri_projected.p[i].x) * 0.5 * width as f32;
+                tri_projected.p[i].y = (1.0 - tri_projected.p[i].y) * 0.5 * height as f32;
+                tri_projected.inv_z[i] = 1.0 / clipped_tri.p[i].z; // z >= 0.1 after near plane clipping
+                tri_projected.shade[i] = shade_vertex(to_world.transform_vec(clipped_tri.p[i]), clipped_tri.n[i], camera.position, CONFIG.lighting.target_specular);
 
             }
-            let avg_z = tri.avg_z(); // Needed for sorting (painters algorithm)
             tri_vec.push(TriToRaster {
                 tri: tri_projected,
                 color,
-                avg_z,
+                texture: None,
             });
             
         }
-    /*
-        7. If the player shot the gun while hovering over any of the targets' triangles with their crosshair, they hit the target,
-        which is why we then return true.
-    */  if gun_shot && !hit && ray_intersects_triangle(&camera, &tri_world) {
-            hit = true;
-        }
         
     }
-    hit
 }
 
-pub fn room_proj_loop(room: &mut Mesh, tri_vec: &mut Vec<TriToRaster>, camera: &Camera, proj_matrix: &Mat4x4) {
+pub fn room_proj_loop(room: &mut Mesh, textures: &RoomTextures, tri_vec: &mut Vec<TriToRaster>, camera: &Camera, proj_matrix: &Mat4x4, width: usize, height: usize) {
 /*
     This function handles rendering preperation for the room's triangles using the following steps:
 */
+    let to_world = camera.view_matrix.quick_inverse();
     for tri in &mut room.tris {
     /* 
         1. check if the triangle is facing away from the camera, in which case we can skip it entirely.
@@ -89,9 +88,12 @@ pub fn room_proj_loop(room: &mut Mesh, tri_vec: &mut Vec<TriToRaster>, camera: &
         }
     
     /*
-        2. Apply colorized shading utilizing the normal of the triangle
+        2. The lighting is computed per vertex from the vertex normals (see step 5), textured surfaces get their texture
     */
-        let color = color_triangle(normal, CONFIG.environment.scene_color);
+        let color = rgb_color(CONFIG.environment.scene_color);
+        let surface = Surface::facing(normal);
+        let texture = textures.index(surface);
+        let world_size = texture.map_or(1.0, |i| textures.textures[i].world_size);
     
     /* 
         3. Convert Triangles' World Space to View Space by multiplying with camera's view matrix.
@@ -106,21 +108,27 @@ pub fn room_proj_loop(room: &mut Mesh, tri_vec: &mut Vec<TriToRaster>, camera: &
         let (clipped, n_clipped) = tri_clip_z(&mut tri_pre_clipping);
 
     /*
-        5. Converts Triangles from camera space to Projection (Screen) space, meaning we can omit z completely and 
-        turn the Vec3d's into Vec2d's (to safe some performance)
+        5. Converts Triangles from camera space to Projection (Screen) space. Only 1/z is kept per point,
+        for the depth test in the rasterizer, along with its light which the rasterizer blends across the triangle
     */
         let mut tri_projected = Triangle2d::new_origin();
-        for n in 0..n_clipped {
+        for clipped_tri in &clipped[..n_clipped] {
             for i in 0..3 {
-                tri_projected.p[i] = proj_matrix.project_vec(clipped[n].p[i]);
-                tri_projected.p[i].x = (1.0 - tri_projected.p[i].x) * 0.5 * CONFIG.display.width as f32;
-                tri_projected.p[i].y = (1.0 - tri_projected.p[i].y) * 0.5 * CONFIG.display.height as f32;
+                tri_projected.p[i] = proj_matrix.project_vec(clipped_tri.p[i]);
+                tri_projected.p[i].x = (1.0 - tri_projected.p[i].x) * 0.5 * width as f32;
+                tri_projected.p[i].y = (1.0 - tri_projected.p[i].y) * 0.5 * height as f32;
+                tri_projected.inv_z[i] = 1.0 / clipped_tri.p[i].z; // z >= 0.1 after near plane clipping
+                let world = to_world.transform_vec(clipped_tri.p[i]);
+                tri_projected.shade[i] = shade_vertex(world, clipped_tri.n[i], camera.position, 0.0);
+                if texture.is_some() {
+                    // Divided by z so the rasterizer can interpolate them perspective correct
+                    tri_projected.uv_z[i] = surface_uv(world, normal, surface) * (tri_projected.inv_z[i] / world_size);
+                }
             }
-            let avg_z = tri.avg_z(); // Needed for sorting (painters algorithm)
             tri_vec.push(TriToRaster {
                 tri: tri_projected,
                 color,
-                avg_z,
+                texture,
             });
         }
         
@@ -128,15 +136,70 @@ pub fn room_proj_loop(room: &mut Mesh, tri_vec: &mut Vec<TriToRaster>, camera: &
 }
 
 
-pub fn color_triangle(normal:Vec3d, color:[u8;3]) -> u32 {
-    let mut brightness = normal.dot(Vec3d::from_tuple(CONFIG.environment.light_direction).normalize());
-    brightness = (sigmoid(brightness) + 0.3).clamp(0.0, 1.0);
-    brightness = brightness.powf(1.2);
+pub fn outside_frustum(center: Vec3d, radius: f32, proj_matrix: &Mat4x4) -> bool {
+/*
+    Tests a bounding sphere (center in view space) against the side planes of the view frustum,
+    read straight from the projection matrix: a point is on screen while -w <= x <= w and -w <= y <= w.
+    The near plane is the one tri_clip_z cuts at, there is no far plane.
+*/
+    let m = &proj_matrix.m;
+    let column = |c: usize| (Vec3d::new(m[0][c], m[1][c], m[2][c]), m[3][c]);
+    let ((x, x_offset), (y, y_offset), (w, w_offset)) = (column(0), column(1), column(3));
+    // Inside is where n·p + d <= 0
+    let planes = [
+        (Vec3d::new(0.0, 0.0, -1.0), 0.1),
+        (x - w, x_offset - w_offset),
+        ((x + w) * -1.0, -(x_offset + w_offset)),
+        (y - w, y_offset - w_offset),
+        ((y + w) * -1.0, -(y_offset + w_offset)),
+    ];
+    planes.iter().any(|&(n, d)| (n.dot(center) + d) / n.length() > radius)
+}
 
-    let r = (color[0] as f32 * brightness) as u32;
-    let g = (color[1] as f32 * brightness) as u32;
-    let b = (color[2] as f32 * brightness) as u32;
+pub fn screen_radius(center: Vec3d, radius: f32, proj_matrix: &Mat4x4, height: usize) -> f32 {
+    // Approximate radius in pixels of a sphere whose center is in view space, the whole screen when the camera is inside
+    if center.z > radius {
+        radius * proj_matrix.m[1][1] / center.z * 0.5 * height as f32
+    } else {
+        height as f32
+    }
+}
 
-    (0xFF << 24) | (r << 16) | (g << 8) | b
+fn surface_uv(world: Vec3d, normal: Vec3d, surface: Surface) -> Vec2d {
+/*
+    Texture coordinates in world units. Floors and ceilings use the ground plane,
+    walls are unrolled horizontally along their own direction so slanted walls are not stretched.
+*/
+    match surface {
+        Surface::Floor | Surface::Ceiling => Vec2d::new(world.x, world.z),
+        Surface::Walls => {
+            let along = Vec3d::new(normal.z, 0.0, -normal.x).normalize();
+            Vec2d::new(world.dot(along), -world.y)
+        }
+    }
 }
 
+pub fn rgb_color(color:[u8;3]) -> u32 {
+    (0xFF << 24) | (color[0] as u32) << 16 | (color[1] as u32) << 8 | color[2] as u32
+}
+
+pub fn project_point(point: Vec3d, camera: &Camera, proj_matrix: &Mat4x4, width: usize, height: usize) -> Option<(i32, i32)> {
+/*
+    Projects a single world space point to pixel coordinates, used for overlays like the replay trails.
+    Points behind the near plane have no sensible screen position and return None.
+*/
+    let view = camera.view_matrix * point;
+    if view.z < 0.1 {
+        return None;
+    }
+    let projected = proj_matrix.project_vec(view);
+    let x = (1.0 - projected.x) * 0.5 * width as f32;
+    let y = (1.0 - projected.y) * 0.5 * height as f32;
+
+    // Bresenham walks every pixel in between, so far off-screen points are dropped instead of drawn towards
+    let limit = 4.0 * width.max(height) as f32;
+    if x.abs() > limit || y.abs() > limit {
+        return None;
+    }
+    Some((x as i32, y as i32))
+}
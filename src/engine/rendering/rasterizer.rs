@@ -1,4 +1,6 @@
-use crate::engine::{Triangle2d, TriToRaster};
+use std::ops::Range;
+
+use crate::engine::{Triangle2d, TriToRaster, core::{Shade, SHADE_CHANNELS}, rendering::SurfaceTexture};
 
 
 pub fn draw_triangle(buf: &mut [u32], tri: &Triangle2d, width: usize, height: usize) {
@@ -35,7 +37,8 @@ pub fn draw_triangle(buf: &mut [u32], tri: &Triangle2d, width: usize, height: us
     );
 }
 
-fn draw_line(buf: &mut [u32], x0: i32, y0: i32, x1: i32, y1: i32, color: u32, width: usize, height: usize) {
+#[allow(clippy::too_many_arguments)]
+pub fn draw_line(buf: &mut [u32], x0: i32, y0: i32, x1: i32, y1: i32, color: u32, width: usize, height: usize) {
     let mut x0 = x0;
     let mut y0 = y0;
     let dx = (x1 - x0).abs();
@@ -61,81 +64,164 @@ fn draw_line(buf: &mut [u32], x0: i32, y0: i32, x1: i32, y1: i32, color: u32, wi
     }
 }
 
-#[inline(always)]
-fn fill_triangle_optimized(
-    buf: &mut [u32],
-    mut x0: i32,
-    mut y0: i32,
-    mut x1: i32,
-    mut y1: i32,
-    mut x2: i32,
-    mut y2: i32,
-    color: u32,
-    width: usize,
-    height: usize,
-) {
-    // Sort vertices by y-coordinate
-    if y0 > y1 {
-        std::mem::swap(&mut y0, &mut y1);
-        std::mem::swap(&mut x0, &mut x1);
-    }
-    if y0 > y2 {
-        std::mem::swap(&mut y0, &mut y2);
-        std::mem::swap(&mut x0, &mut x2);
-    }
-    if y1 > y2 {
-        std::mem::swap(&mut y1, &mut y2);
-        std::mem::swap(&mut x1, &mut x2);
+// Vertices are snapped to 1/256 of a pixel, so moving targets slide smoothly instead of jumping whole pixels
+const SUBPIXEL_BITS: u32 = 8;
+const SUBPIXEL_ONE: i64 = 1 << SUBPIXEL_BITS;
+
+struct Edge {
+/*
+    Edge function of the line a -> b, evaluated at pixel centres in fixed point:
+    value(x, y) = value_origin + x * step_x + y * step_y, a pixel is on the inner side when it is >= 0.
+*/
+    value_origin: i64,
+    step_x: i64,
+    step_y: i64,
+}
+impl Edge {
+    fn new(a: (i64, i64), b: (i64, i64)) -> Self {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    /*
+        Top-left fill rule: a pixel centre exactly on an edge belongs to the triangle only if the edge is
+        a top edge (horizontal, inside below) or a left edge (inside to the right). The neighbouring triangle
+        sees the same edge the other way round, so every shared pixel goes to exactly one of them.
+    */  let bias = if dy < 0 || (dy == 0 && dx > 0) { 0 } else { -1 };
+        let centre = SUBPIXEL_ONE / 2;
+        Self {
+            value_origin: dx * (centre - a.1) - dy * (centre - a.0) + bias,
+            step_x: -dy * SUBPIXEL_ONE,
+            step_y: dx * SUBPIXEL_ONE,
+        }
     }
 
-    let total_height = y2 - y0;
-    if total_height == 0 {
-        return;
+    fn row_value(&self, y: i32) -> i64 {
+        self.value_origin + self.step_y * y as i64
     }
 
-    for i in 0..=total_height {
-        let y = y0 + i;
-        if y < 0 {
-            continue;
-        }
-        if y >= height as i32 {
-            break;
+    fn clip_span(&self, row_value: i64, span: &mut (i64, i64)) {
+        // Narrows span to the x where row_value + x * step_x >= 0, solved exactly in integers
+        match self.step_x.signum() {
+            1 => span.0 = span.0.max(-(row_value.div_euclid(self.step_x))),
+            -1 => span.1 = span.1.min(row_value.div_euclid(-self.step_x)),
+            _ if row_value < 0 => *span = (1, 0),
+            _ => {}
         }
+    }
+}
 
-        let second_half = i > (y1 - y0) || y1 == y0;
-        let segment_height = if second_half { y2 - y1 } else { y1 - y0 };
-        if segment_height == 0 {
-            continue;
-        }
+#[derive(Clone, Copy)]
+struct Varying {
+    // A value blended from the three vertices, at the first pixel of a span and its change per pixel
+    start: f32,
+    step: f32,
+}
+impl Varying {
+    fn at(&self, i: usize) -> f32 {
+        self.start + self.step * i as f32
+    }
+}
 
-        let alpha = i as f32 / total_height as f32;
-        let beta = (i - if second_half { y1 - y0 } else { 0 }) as f32 / segment_height as f32;
+struct SpanVaryings {
+    shade: [Varying; SHADE_CHANNELS],
+    uv_z: [Varying; 2], // u/z and v/z, divided by the interpolated 1/z they give perspective correct texture coordinates
+}
 
-        let ax = x0 as f32 + (x2 - x0) as f32 * alpha;
-        let bx = if second_half {
-            x1 as f32 + (x2 - x1) as f32 * beta
-        } else {
-            x0 as f32 + (x1 - x0) as f32 * beta
-        };
+fn rasterize_triangle(tri: &Triangle2d, width: usize, rows: &Range<i32>, mut draw_span: impl FnMut(i32, Range<i32>, Varying, SpanVaryings)) {
+/*
+    Calls draw_span(y, x range, 1/z, other varyings) for each row of the triangle within rows.
+    Coverage is decided by integer edge functions on fixed point vertices, which makes meshes watertight:
+    triangles sharing an edge neither leave gaps nor draw a pixel twice.
+    Every row is computed on its own, so a triangle drawn tile by tile gives exactly the same pixels.
+*/
+    let fixed = |i: usize| ((tri.p[i].x * SUBPIXEL_ONE as f32).round() as i64, (tri.p[i].y * SUBPIXEL_ONE as f32).round() as i64);
+    let mut v = [fixed(0), fixed(1), fixed(2)];
+    let mut inv_z = tri.inv_z;
+    let mut shade = tri.shade.map(|s| s.channels());
+    let mut uv_z = tri.uv_z.map(|uv| [uv.x, uv.y]);
 
-        let mut minx = if ax < bx { ax as i32 } else { bx as i32 };
-        let mut maxx = if ax < bx { bx as i32 } else { ax as i32 };
+    let mut area = (v[1].0 - v[0].0) * (v[2].1 - v[0].1) - (v[1].1 - v[0].1) * (v[2].0 - v[0].0);
+    if area == 0 {
+        return;
+    }
+    if area < 0 {
+        // Same winding for every triangle, so "inside" is always the positive side of the edges
+        v.swap(1, 2);
+        inv_z.swap(1, 2);
+        shade.swap(1, 2);
+        uv_z.swap(1, 2);
+        area = -area;
+    }
+    // Edge i lies opposite vertex i, so its value is that vertex's barycentric weight (times area)
+    let edges = [Edge::new(v[1], v[2]), Edge::new(v[2], v[0]), Edge::new(v[0], v[1])];
 
-        // Clip to screen bounds
-        minx = minx.max(0);
-        maxx = maxx.min(width as i32 - 1);
+    let pixel = |value: i64| (value >> SUBPIXEL_BITS) as i32;
+    let top = pixel(v[0].1.min(v[1].1).min(v[2].1)).max(rows.start);
+    let bottom = (pixel(v[0].1.max(v[1].1).max(v[2].1)) + 1).min(rows.end);
+    let inv_area = 1.0 / area as f32;
+    let step = |attr: [f32; 3]| edges.iter().zip(attr).map(|(e, a)| e.step_x as f32 * a).sum::<f32>() * inv_area;
+    let shade_channel = |c: usize| shade.map(|s| s[c]);
+    let uv_channel = |c: usize| uv_z.map(|uv| uv[c]);
+    let z_step = step(inv_z);
+    let shade_steps: [f32; SHADE_CHANNELS] = std::array::from_fn(|c| step(shade_channel(c)));
+    let uv_steps: [f32; 2] = std::array::from_fn(|c| step(uv_channel(c)));
 
-        if minx > maxx {
+    for y in top..bottom {
+        let values = edges.each_ref().map(|e| e.row_value(y));
+        let mut span = (0, width as i64 - 1);
+        for (edge, value) in edges.iter().zip(values) {
+            edge.clip_span(value, &mut span);
+        }
+        if span.0 > span.1 {
             continue;
         }
-
-        // Use memset-like operation for horizontal spans
-        let row_start = (y as usize) * width + (minx as usize);
-        let span_length = (maxx - minx + 1) as usize;
-        buf[row_start..row_start + span_length].fill(color);
+        // Every varying is a barycentric blend of the vertices' values, which makes it linear along the row
+        let start = |attr: [f32; 3], step: f32| Varying {
+            start: values.iter().zip(attr).map(|(&w, a)| w as f32 * a).sum::<f32>() * inv_area + step * span.0 as f32,
+            step,
+        };
+        let varyings = SpanVaryings {
+            shade: std::array::from_fn(|c| start(shade_channel(c), shade_steps[c])),
+            uv_z: std::array::from_fn(|c| start(uv_channel(c), uv_steps[c])),
+        };
+        draw_span(y, span.0 as i32..span.1 as i32 + 1, start(inv_z, z_step), varyings);
     }
 }
 
+#[inline(always)]
+fn fill_triangle_optimized(
+    buf: &mut [u32],
+    depth_buf: &mut [f32],
+    tri: &Triangle2d,
+    color: u32,
+    texture: Option<&SurfaceTexture>,
+    width: usize,
+    rows: &Range<i32>,
+) {
+/*
+    buf and depth_buf only hold the given rows of the frame (a tile).
+    Depth test per pixel: a larger 1/z is closer to the camera. Only pixels in front of
+    everything drawn so far are written, so the order triangles arrive in does not matter.
+    Flat untextured triangles (the same light on every vertex) are coloured once instead of per pixel.
+*/
+    let flat_shade = tri.shade[0] == tri.shade[1] && tri.shade[1] == tri.shade[2];
+    let flat = (flat_shade && texture.is_none()).then(|| tri.shade[0].apply(color));
+    rasterize_triangle(tri, width, rows, |y, xs, depth, varyings| {
+        let row_start = ((y - rows.start) as usize) * width;
+        let pixels = row_start + xs.start as usize..row_start + xs.end as usize;
+        for (i, idx) in pixels.enumerate() {
+            let z = depth.at(i);
+            if z <= depth_buf[idx] {
+                continue;
+            }
+            depth_buf[idx] = z;
+            buf[idx] = flat.unwrap_or_else(|| {
+                let color = texture.map_or(color, |t| t.sample(varyings.uv_z[0].at(i) / z, varyings.uv_z[1].at(i) / z));
+                let shade = if flat_shade { tri.shade[0] } else { Shade::from_channels(varyings.shade.map(|s| s.at(i).max(0.0))) };
+                shade.apply(color)
+            });
+        }
+    });
+}
+
 #[inline(always)]
 pub fn put_pixel(buf: &mut [u32], x: i32, y: i32, color: u32, width: usize, height: usize) {
     if x >= 0
@@ -147,19 +233,128 @@ pub fn put_pixel(buf: &mut [u32], x: i32, y: i32, color: u32, width: usize, heig
     }
 }
 
-pub fn render_triangles(buffer: &mut [u32], list_triangles: &Vec<TriToRaster>, width: usize, height: usize) {
+pub fn render_triangles(buffer: &mut [u32], depth_buffer: &mut [f32], list_triangles: &[TriToRaster], textures: &[SurfaceTexture], width: usize, rows: Range<i32>) {
     for tri in list_triangles.iter() {
-        fill_triangle_optimized(
-            buffer,
-            tri.tri.p[0].x as i32,
-            tri.tri.p[0].y as i32,
-            tri.tri.p[1].x as i32,
-            tri.tri.p[1].y as i32,
-            tri.tri.p[2].x as i32,
-            tri.tri.p[2].y as i32,
-            tri.color,
-            width,
-            height,
-        );
-    }
-}
\ No newline at end of file
+        let texture = tri.texture.map(|i| &textures[i]);
+        fill_triangle_optimized(buffer, depth_buffer, &tri.tri, tri.color, texture, width, &rows);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::engine::Vec2d;
+
+    const W: usize = 64;
+    const H: usize = 48;
+
+    fn tri(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> Triangle2d {
+        Triangle2d { p: [Vec2d::new(a.0, a.1), Vec2d::new(b.0, b.1), Vec2d::new(c.0, c.1)], inv_z: [1.0; 3], shade: [Shade::UNLIT; 3], uv_z: [Vec2d::zero(); 3] }
+    }
+
+    fn coverage(tris: &[Triangle2d]) -> Vec<u32> {
+        // How often each pixel is drawn
+        let mut counts = vec![0; W * H];
+        for t in tris {
+            rasterize_triangle(t, W, &(0..H as i32), |y, xs, _, _| {
+                for x in xs {
+                    counts[y as usize * W + x as usize] += 1;
+                }
+            });
+        }
+        counts
+    }
+
+    fn jittered_grid(cells: usize, jitter: f32, seed: u64) -> Vec<Triangle2d> {
+    /*
+        Splits the whole W x H screen into quads with randomly moved inner corners (at sub-pixel positions),
+        each cut into two triangles along an alternating diagonal and with alternating winding.
+        The jitter has to stay below a quarter of a cell, so that every quad stays convex.
+    */
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (cw, ch) = (W as f32 / cells as f32, H as f32 / cells as f32);
+        let mut points = vec![vec![(0.0, 0.0); cells + 1]; cells + 1];
+        for (j, row) in points.iter_mut().enumerate() {
+            for (i, point) in row.iter_mut().enumerate() {
+                let inner = i > 0 && j > 0 && i < cells && j < cells;
+                let offset = |rng: &mut StdRng| if inner { rng.gen_range(-jitter..jitter) } else { 0.0 };
+                *point = (i as f32 * cw + offset(&mut rng), j as f32 * ch + offset(&mut rng));
+            }
+        }
+        let mut tris = Vec::new();
+        for j in 0..cells {
+            for i in 0..cells {
+                let (a, b, c, d) = (points[j][i], points[j][i + 1], points[j + 1][i + 1], points[j + 1][i]);
+                if (i + j) % 2 == 0 {
+                    tris.push(tri(a, b, c));
+                    tris.push(tri(a, d, c));
+                } else {
+                    tris.push(tri(b, c, d));
+                    tris.push(tri(d, a, b));
+                }
+            }
+        }
+        tris
+    }
+
+    #[test]
+    fn shared_diagonal_is_drawn_once() {
+        // A quad with vertices off the pixel grid, split along a diagonal that passes through pixel centres
+        let (a, b, c, d) = ((2.5, 2.5), (40.5, 2.5), (40.5, 40.5), (2.5, 40.5));
+        let counts = coverage(&[tri(a, b, c), tri(a, c, d)]);
+        assert!(counts.iter().all(|&n| n <= 1), "pixels drawn twice");
+        for y in 3..40 {
+            for x in 3..40 {
+                assert_eq!(counts[y * W + x], 1, "gap at ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn triangle_fan_is_watertight() {
+        // Many thin triangles around a sub-pixel centre, like the cap of a sphere
+        let centre = (31.3, 23.7);
+        let n = 40;
+        let point = |i: usize| {
+            let angle = i as f32 / n as f32 * std::f32::consts::TAU;
+            (centre.0 + angle.cos() * 100.0, centre.1 + angle.sin() * 100.0)
+        };
+        let fan: Vec<Triangle2d> = (0..n).map(|i| tri(centre, point(i), point(i + 1))).collect();
+        assert!(coverage(&fan).iter().all(|&n| n == 1));
+    }
+
+    #[test]
+    fn jittered_meshes_cover_every_pixel_once() {
+        for seed in 0..20 {
+            let counts = coverage(&jittered_grid(8, 1.4, seed));
+            assert!(counts.iter().all(|&n| n == 1), "overlap or gap with seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn tiles_match_the_full_frame() {
+        let tris = jittered_grid(6, 3.0, 7);
+        let mut rows_drawn = vec![Vec::new(); H];
+        for t in &tris {
+            rasterize_triangle(t, W, &(0..H as i32), |y, xs, _, _| rows_drawn[y as usize].push(xs));
+        }
+        let mut tiled = vec![Vec::new(); H];
+        for tile in (0..H as i32).step_by(5) {
+            for t in &tris {
+                rasterize_triangle(t, W, &(tile..(tile + 5).min(H as i32)), |y, xs, _, _| tiled[y as usize].push(xs));
+            }
+        }
+        assert_eq!(rows_drawn, tiled);
+    }
+
+    #[test]
+    fn sub_pixel_vertices_are_not_truncated() {
+        // Covers the pixel centres 0.5, 1.5 and 2.5 in x. Truncating to 0 and 2 would lose one column
+        let (l, r, t, b) = (0.4, 2.6, 10.0, 20.0);
+        let counts = coverage(&[tri((l, t), (r, t), (r, b)), tri((l, t), (r, b), (l, b))]);
+        let row: Vec<u32> = counts[15 * W..15 * W + 4].to_vec();
+        assert_eq!(row, vec![1, 1, 1, 0]);
+    }
+}
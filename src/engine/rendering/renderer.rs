@@ -0,0 +1,296 @@
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+
+use crate::engine::{
+    camera::Camera, core::{CONFIG, Mat4x4, Mesh, TriToRaster}, rendering::{outside_frustum, render_triangles, room_proj_loop, screen_radius, target_proj_loop, tri_clip_xy, RoomTextures, SurfaceTexture}, scenario::{RoomStyle, Target, TargetMeshes}
+};
+
+const TILE_ROWS: usize = 32; // Tiles are full-width bands, so each one is a contiguous part of the buffers
+
+const MAX_SUPERSAMPLING: usize = 4;
+const MIN_RENDER_SCALE: f32 = 0.25;
+
+pub struct SceneRenderer {
+/*
+    Owns the pre-allocated triangle buffers and draws the room and its targets into a pixel buffer.
+    Shared by the live runtime and the replay viewer so both produce the same image for the same state.
+
+    The scene is rendered at render_width x render_height, which is the window size times render_scale.
+    With supersampling it is rasterized into a buffer N times as wide and high,
+    then every N x N block is averaged into one pixel (anti-aliasing).
+    A render scale below 1 renders fewer pixels and stretches the result over the window.
+*/
+    tri_vec: Vec<TriToRaster>,
+    tri_clipped: Vec<TriToRaster>,
+    raster_vec: Vec<TriToRaster>, // All triangles after screen edge clipping
+    tiles: Vec<Vec<TriToRaster>>, // The triangles touching each tile, in drawing order
+    depth_buffer: Vec<f32>, // 1/z of the closest triangle drawn so far, per sample
+    sample_buffer: Vec<u32>, // Colour per sample, only used with supersampling
+    scaled_buffer: Vec<u32>, // The scene at render resolution, only used with a render scale below 1
+    room_textures: RoomTextures,
+    target_meshes: TargetMeshes,
+    pool: Option<ThreadPool>, // Workers live as long as the renderer, None renders on the calling thread
+    pub supersampling: usize,
+    pub render_scale: f32,
+    pub proj_matrix: Mat4x4,
+    pub width: usize, // Size of the window's pixel buffer, which overlays are drawn in
+    pub height: usize,
+    pub render_width: usize,
+    pub render_height: usize,
+}
+
+impl SceneRenderer {
+    pub fn new(width: usize, height: usize, room_style: &RoomStyle) -> Self {
+        let threads = match CONFIG.display.render_threads {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        let pool = if threads > 1 {
+            ThreadPoolBuilder::new().num_threads(threads).build().map_err(|e| eprintln!("Warning: Failed to start render threads: {}", e)).ok()
+        } else {
+            None
+        };
+        let mut renderer = Self {
+            tri_vec: Vec::with_capacity(1024),
+            tri_clipped: Vec::with_capacity(4),
+            raster_vec: Vec::with_capacity(1024),
+            tiles: Vec::new(),
+            depth_buffer: Vec::new(),
+            sample_buffer: Vec::new(),
+            scaled_buffer: Vec::new(),
+            room_textures: RoomTextures::load(room_style),
+            target_meshes: TargetMeshes::new(),
+            pool,
+            supersampling: CONFIG.display.supersampling.clamp(1, MAX_SUPERSAMPLING),
+            render_scale: CONFIG.display.render_scale.clamp(MIN_RENDER_SCALE, 1.0),
+            proj_matrix: Mat4x4::projection(width as f32, height as f32),
+            width,
+            height,
+            render_width: width,
+            render_height: height,
+        };
+        renderer.resize(width, height);
+        renderer
+    }
+
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.render_width = ((width as f32 * self.render_scale).round() as usize).clamp(1.min(width), width);
+        self.render_height = ((height as f32 * self.render_scale).round() as usize).clamp(1.min(height), height);
+        if self.is_scaled() {
+            self.scaled_buffer.resize(self.render_width * self.render_height, 0);
+        }
+        let (sample_width, sample_height) = (self.render_width * self.supersampling, self.render_height * self.supersampling);
+        self.depth_buffer.resize(sample_width * sample_height, 0.0);
+        if self.supersampling > 1 {
+            self.sample_buffer.resize(sample_width * sample_height, 0);
+        }
+        self.tiles.resize(sample_height.div_ceil(TILE_ROWS), Vec::new());
+        self.proj_matrix = Mat4x4::projection(width as f32, height as f32);
+    }
+
+    pub fn render(&mut self, pixel_buffer: &mut [u32], room: &mut Mesh, targets: &mut [Target], camera: &Camera, alpha: f32) {
+        let proj_matrix = &self.proj_matrix;
+        self.tri_vec.clear();
+
+        let (render_width, render_height) = (self.render_width, self.render_height);
+        room_proj_loop(room, &self.room_textures, &mut self.tri_vec, camera, proj_matrix, render_width, render_height);
+
+        for target in targets.iter() {
+            let position = target.render_position(alpha);
+
+            // Targets entirely outside the view are skipped before any triangle work
+            let center = camera.view_matrix * position;
+            if outside_frustum(center, target.bounds, proj_matrix) {
+                continue;
+            }
+            // Round targets are tessellated finer the larger they appear
+            let radius_px = screen_radius(center, target.bounds, proj_matrix, render_height);
+            let tris = self.target_meshes.for_screen_radius(target, radius_px);
+            target_proj_loop(tris, position, &mut self.tri_vec, camera, proj_matrix, render_width, render_height);
+        }
+
+    /*
+        We now iterate through the Triangle Vector, which contains the 2d Triangles of both the Targets and the Room.
+        We first clipp the triangles at the screen edges and then use the fill function to draw them onto the screen.
+        The depth buffer decides per pixel what is visible, so the triangles can come in any order.
+    */
+        let samples = self.supersampling;
+        self.raster_vec.clear();
+        for tri2d in &mut self.tri_vec {
+            self.tri_clipped.clear();
+            tri_clip_xy(tri2d, &mut self.tri_clipped, render_width, render_height);
+            for tri in &mut self.tri_clipped {
+                for p in &mut tri.tri.p {
+                    *p = *p * samples as f32;
+                }
+            }
+            self.raster_vec.extend_from_slice(&self.tri_clipped);
+        }
+
+        // Clearing the pixels and depths of the last frame (a 1/z of 0 is infinitely far away)
+        self.depth_buffer.fill(0.0);
+        let scaled = self.is_scaled();
+        let resolved = if scaled { &mut self.scaled_buffer[..] } else { &mut *pixel_buffer };
+        let target = if samples > 1 { &mut self.sample_buffer[..] } else { resolved };
+        target.fill(0);
+
+        let sample_width = render_width * samples;
+        let pool = self.pool.as_ref();
+        match pool {
+            Some(pool) => render_tiles(pool, target, &mut self.depth_buffer, &mut self.tiles, &self.raster_vec, &self.room_textures.textures, sample_width),
+            None => render_triangles(target, &mut self.depth_buffer, &self.raster_vec, &self.room_textures.textures, sample_width, 0..(render_height * samples) as i32),
+        }
+
+        let resolved = if scaled { &mut self.scaled_buffer[..] } else { &mut *pixel_buffer };
+        if samples > 1 {
+            downsample(&self.sample_buffer, resolved, render_width, samples, pool);
+        }
+        if scaled {
+            upscale(&self.scaled_buffer, render_width, render_height, pixel_buffer, self.width, pool);
+        }
+    }
+
+    fn is_scaled(&self) -> bool {
+        self.render_width != self.width || self.render_height != self.height
+    }
+}
+
+fn render_tiles(pool: &ThreadPool, pixels: &mut [u32], depths: &mut [f32], tiles: &mut [Vec<TriToRaster>], raster_vec: &[TriToRaster], textures: &[SurfaceTexture], width: usize) {
+/*
+    1. Every triangle is put into the bin of each tile its rows overlap
+    2. The pool's threads take tiles one at a time and rasterize only that tile's rows
+
+    Each pixel still sees the same triangles in the same order as in single-threaded mode,
+    so the image is identical, just spread over the cores.
+*/
+    for tile in tiles.iter_mut() {
+        tile.clear();
+    }
+    let last_tile = tiles.len() as i32 - 1;
+    for tri in raster_vec {
+        let ys = tri.tri.p.map(|p| p.y);
+        let top = ys[0].min(ys[1]).min(ys[2]).floor() as i32 / TILE_ROWS as i32;
+        let bottom = ys[0].max(ys[1]).max(ys[2]).ceil() as i32 / TILE_ROWS as i32;
+        for tile in top.max(0)..=bottom.min(last_tile) {
+            tiles[tile as usize].push(*tri);
+        }
+    }
+
+    let tile_len = TILE_ROWS * width;
+    if tile_len == 0 {
+        return; // Minimized window, nothing to draw into
+    }
+    pool.install(|| {
+        pixels.par_chunks_mut(tile_len).zip(depths.par_chunks_mut(tile_len)).zip(tiles.par_iter()).enumerate().for_each(|(i, ((pixels, depths), tris))| {
+            let first_row = (i * TILE_ROWS) as i32;
+            let rows = first_row..first_row + (pixels.len() / width) as i32;
+            render_triangles(pixels, depths, tris, textures, width, rows);
+        });
+    });
+}
+
+fn downsample(samples: &[u32], pixel_buffer: &mut [u32], width: usize, factor: usize, pool: Option<&ThreadPool>) {
+    // Box filter: every output pixel is the average colour of its factor x factor samples
+    if width == 0 {
+        return;
+    }
+    let sample_width = width * factor;
+    for_each_row(pixel_buffer, width, pool, |y, row| {
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let mut sum = [0u32; 3];
+            for sy in y * factor..(y + 1) * factor {
+                let start = sy * sample_width + x * factor;
+                for &sample in &samples[start..start + factor] {
+                    sum[0] += (sample >> 16) & 0xFF;
+                    sum[1] += (sample >> 8) & 0xFF;
+                    sum[2] += sample & 0xFF;
+                }
+            }
+            let n = (factor * factor) as u32;
+            *pixel = 0xFF000000 | (sum[0] / n) << 16 | (sum[1] / n) << 8 | (sum[2] / n);
+        }
+    });
+}
+
+fn upscale(src: &[u32], src_width: usize, src_height: usize, pixel_buffer: &mut [u32], width: usize, pool: Option<&ThreadPool>) {
+    // Nearest neighbour, so a render scale of 0.5 simply shows every rendered pixel as 2x2
+    if width == 0 {
+        return;
+    }
+    let height = pixel_buffer.len() / width;
+    for_each_row(pixel_buffer, width, pool, |y, row| {
+        let src_row = &src[(y * src_height / height) * src_width..][..src_width];
+        for (x, pixel) in row.iter_mut().enumerate() {
+            *pixel = src_row[x * src_width / width];
+        }
+    });
+}
+
+fn for_each_row(buffer: &mut [u32], width: usize, pool: Option<&ThreadPool>, resolve_row: impl Fn(usize, &mut [u32]) + Sync) {
+    // Rows are spread over the pool, without one they are resolved right here
+    match pool {
+        Some(pool) => pool.install(|| buffer.par_chunks_mut(width).enumerate().for_each(|(y, row)| resolve_row(y, row))),
+        None => buffer.chunks_mut(width).enumerate().for_each(|(y, row)| resolve_row(y, row)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::engine::{Triangle2d, Vec2d, core::Shade};
+
+    const W: usize = 200;
+    const H: usize = 150; // Not a multiple of TILE_ROWS, so the last tile is cut short
+
+    fn scene(seed: u64) -> Vec<TriToRaster> {
+        // Overlapping triangles at random depths and shades, some spanning several tiles
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..300).map(|_| {
+            let mut point = || Vec2d::new(rng.gen_range(0.0..W as f32), rng.gen_range(0.0..H as f32));
+            let p = [point(), point(), point()];
+            let shade = Shade { diffuse: [rng.gen_range(0.2..1.0); 3], specular: [rng.gen_range(0.0..0.2); 3] };
+            TriToRaster {
+                tri: Triangle2d { p, inv_z: [rng.gen_range(0.01..1.0); 3], shade: [shade; 3], uv_z: [Vec2d::zero(); 3] },
+                color: rng.gen_range(0..0xFFFFFF),
+                texture: None,
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn pooled_tiles_match_single_threaded() {
+        let pool = ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        for seed in 0..5 {
+            let tris = scene(seed);
+            let (mut single, mut single_depth) = (vec![0; W * H], vec![0.0; W * H]);
+            render_triangles(&mut single, &mut single_depth, &tris, &[], W, 0..H as i32);
+
+            let (mut pooled, mut pooled_depth) = (vec![0; W * H], vec![0.0; W * H]);
+            let mut tiles = vec![Vec::new(); H.div_ceil(TILE_ROWS)];
+            render_tiles(&pool, &mut pooled, &mut pooled_depth, &mut tiles, &tris, &[], W);
+
+            assert!(single == pooled, "image differs with seed {}", seed);
+            assert!(single_depth == pooled_depth, "depths differ with seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn pooled_resolve_matches_inline() {
+        let pool = ThreadPoolBuilder::new().num_threads(3).build().unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+        let samples: Vec<u32> = (0..W * H * 4).map(|_| rng.gen()).collect();
+
+        let (mut inline, mut pooled) = (vec![0; W * H], vec![0; W * H]);
+        downsample(&samples, &mut inline, W, 2, None);
+        downsample(&samples, &mut pooled, W, 2, Some(&pool));
+        assert!(inline == pooled);
+
+        let (mut inline_up, mut pooled_up) = (vec![0; 333 * 250], vec![0; 333 * 250]);
+        upscale(&inline, W, H, &mut inline_up, 333, None);
+        upscale(&inline, W, H, &mut pooled_up, 333, Some(&pool));
+        assert!(inline_up == pooled_up);
+    }
+}
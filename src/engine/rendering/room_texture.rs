@@ -0,0 +1,87 @@
+use crate::engine::{core::{CONFIG, TEXTURE_DIR}, rendering::{rgb_color, Texture}, scenario::{RoomStyle, Surface, SurfaceStyle}};
+
+// Resolution of one repeat of the procedural patterns
+const PATTERN_SIZE: usize = 64;
+const GRID_LINE_WIDTH: usize = 2;
+
+pub struct SurfaceTexture {
+    pub texture: Texture,
+    pub world_size: f32, // World units one repeat of the texture covers
+}
+impl SurfaceTexture {
+    pub fn load(style: &SurfaceStyle) -> Option<Self> {
+        let base = CONFIG.environment.scene_color;
+        match *style {
+            SurfaceStyle::Plain => None,
+            SurfaceStyle::Grid { size } => Some(Self { texture: grid(base), world_size: size }),
+            // One repeat holds 2 x 2 squares
+            SurfaceStyle::Checker { size } => Some(Self { texture: checker(base), world_size: size * 2.0 }),
+            SurfaceStyle::Png { file, size } => {
+                let path = TEXTURE_DIR.join(file);
+                match Texture::load_from_png(&path.to_string_lossy()) {
+                    Ok(texture) => Some(Self { texture, world_size: size }),
+                    Err(e) => {
+                        eprintln!("Warning: Failed to load texture {}: {}", path.display(), e);
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub fn sample(&self, u: f32, v: f32) -> u32 {
+        // u and v count texture repeats, the texture wraps around
+        let (w, h) = (self.texture.width, self.texture.height);
+        let x = (u.rem_euclid(1.0) * w as f32) as usize;
+        let y = (v.rem_euclid(1.0) * h as f32) as usize;
+        self.texture.data[y.min(h - 1) * w + x.min(w - 1)]
+    }
+}
+
+pub struct RoomTextures {
+/*
+    The textures of a scenario's room surfaces, loaded once when the scene renderer is created.
+    Surfaces without a texture are drawn in the plain scene colour.
+*/
+    pub textures: Vec<SurfaceTexture>,
+    surfaces: [Option<usize>; 3], // Index into textures per Surface
+}
+impl RoomTextures {
+    pub fn load(style: &RoomStyle) -> Self {
+        let mut room = Self { textures: Vec::new(), surfaces: [None; 3] };
+        for surface in Surface::ALL {
+            if let Some(texture) = SurfaceTexture::load(style.surface(surface)) {
+                room.textures.push(texture);
+                room.surfaces[surface as usize] = Some(room.textures.len() - 1);
+            }
+        }
+        room
+    }
+
+    pub fn index(&self, surface: Surface) -> Option<usize> {
+        self.surfaces[surface as usize]
+    }
+}
+
+fn scale([r, g, b]: [u8; 3], factor: f32) -> [u8; 3] {
+    [r, g, b].map(|c| (c as f32 * factor).min(255.0) as u8)
+}
+
+fn grid(base: [u8; 3]) -> Texture {
+    // One cell of the scene colour, bordered by lighter lines on its top and left edge
+    let (background, line) = (rgb_color(base), rgb_color(scale(base, 1.8)));
+    let data = (0..PATTERN_SIZE * PATTERN_SIZE)
+        .map(|i| if i % PATTERN_SIZE < GRID_LINE_WIDTH || i / PATTERN_SIZE < GRID_LINE_WIDTH { line } else { background })
+        .collect();
+    Texture { width: PATTERN_SIZE, height: PATTERN_SIZE, data }
+}
+
+fn checker(base: [u8; 3]) -> Texture {
+    let (light, dark) = (rgb_color(scale(base, 1.4)), rgb_color(scale(base, 0.7)));
+    let half = PATTERN_SIZE / 2;
+    let data = (0..PATTERN_SIZE * PATTERN_SIZE)
+        .map(|i| if (i % PATTERN_SIZE / half) == (i / PATTERN_SIZE / half) { light } else { dark })
+        .collect();
+    Texture { width: PATTERN_SIZE, height: PATTERN_SIZE, data }
+}
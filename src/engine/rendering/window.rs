@@ -24,9 +24,18 @@ pub fn event_loop_setup() -> Result<(EventLoop<()>, Window), EngineError> {
 
 
 
+    grab_cursor(&window);
+
+    Ok((event_loop, window))
+}
+
+pub fn grab_cursor(window: &Window) {
     let _ = window.set_cursor_grab(CursorGrabMode::Confined)
         .or_else(|_| window.set_cursor_grab(CursorGrabMode::Locked));
     window.set_cursor_visible(false);
+}
 
-    Ok((event_loop, window))
+pub fn release_cursor(window: &Window) {
+    let _ = window.set_cursor_grab(CursorGrabMode::None);
+    window.set_cursor_visible(true);
 }
@@ -0,0 +1,358 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use winit::{event::MouseButton, keyboard::KeyCode};
+
+use crate::engine::{EngineError, Vec3d, camera::Camera, scenario::TargetVec, History, HISTORY_PATH, CONFIG, REPLAY_DIR, find_scenario, simulation::Simulation};
+
+/*
+    Replay files are a small header followed by a flat list of input events.
+    All numbers are little endian. Each event is a one byte tag plus its payload:
+
+    header:  "R3DR" | version u8 | seed u64 | sensitivity f32 | move_speed f32 | sphere_detail u32 | start_on_first_hit u8 | name_len u16 | name
+             (start_on_first_hit was added in version 2, version 1 files are read without it)
+    events:  0 Frame(delta_time f32)
+             1 MouseMotion(dx f32, dy f32)
+             2 KeyPressed(key u8)     3 KeyReleased(key u8)
+             4 MousePressed(btn u8)   5 MouseReleased(btn u8)
+
+    A Frame event closes a frame: every event before it was applied before that frame was simulated.
+*/
+const MAGIC: &[u8; 4] = b"R3DR";
+const VERSION: u8 = 2;
+
+/*
+    State files store what every frame looked like, so the viewer can seek freely without re-simulating:
+
+    header:  "R3DS" | version u8 | name_len u16 | name
+    frames:  time f32 | position 3*f32 | yaw f32 | pitch f32 | flags u8 | target_count u8 | targets
+    target:  id u32 | position 3*f32
+*/
+const STATE_MAGIC: &[u8; 4] = b"R3DS";
+const STATE_VERSION: u8 = 1;
+pub const STATE_EXTENSION: &str = "r3ds"; // Saved next to the input replay with the same file stem
+pub const FLAG_SHOT: u8 = 1;
+pub const FLAG_HIT: u8 = 2;
+
+// Only inputs that reach the simulation are recorded, everything else is window handling
+const RECORDED_KEYS: [KeyCode; 6] = [KeyCode::KeyW, KeyCode::KeyA, KeyCode::KeyS, KeyCode::KeyD, KeyCode::Space, KeyCode::ShiftLeft];
+const RECORDED_BUTTONS: [MouseButton; 3] = [MouseButton::Left, MouseButton::Right, MouseButton::Middle];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplayEvent {
+    Frame { delta_time: f32 },
+    MouseMotion { dx: f32, dy: f32 },
+    KeyPressed(KeyCode),
+    KeyReleased(KeyCode),
+    MousePressed(MouseButton),
+    MouseReleased(MouseButton),
+}
+
+pub struct Recording {
+    pub scenario: String,
+    pub seed: u64,
+    pub sensitivity: f32,
+    pub move_speed: f32,
+    pub sphere_detail: u32,
+    pub start_on_first_hit: bool,
+    pub events: Vec<ReplayEvent>,
+}
+impl Recording {
+    pub fn new(scenario: &str, seed: u64) -> Self {
+        Self {
+            scenario: scenario.to_string(),
+            seed,
+            sensitivity: CONFIG.input.sensitivity,
+            move_speed: CONFIG.input.move_speed,
+            sphere_detail: CONFIG.targets.sphere_detail as u32,
+            start_on_first_hit: CONFIG.run.start_on_first_hit,
+            events: Vec::with_capacity(1 << 16),
+        }
+    }
+
+    pub fn push(&mut self, event: ReplayEvent) {
+        let recorded = match event {
+            ReplayEvent::KeyPressed(key) | ReplayEvent::KeyReleased(key) => RECORDED_KEYS.contains(&key),
+            ReplayEvent::MousePressed(btn) | ReplayEvent::MouseReleased(btn) => RECORDED_BUTTONS.contains(&btn),
+            _ => true,
+        };
+        if recorded {
+            self.events.push(event);
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(32 + self.scenario.len() + self.events.len() * 9);
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.extend_from_slice(&self.seed.to_le_bytes());
+        out.extend_from_slice(&self.sensitivity.to_le_bytes());
+        out.extend_from_slice(&self.move_speed.to_le_bytes());
+        out.extend_from_slice(&self.sphere_detail.to_le_bytes());
+        out.push(self.start_on_first_hit as u8);
+        out.extend_from_slice(&(self.scenario.len() as u16).to_le_bytes());
+        out.extend_from_slice(self.scenario.as_bytes());
+
+        for event in &self.events {
+            match *event {
+                ReplayEvent::Frame { delta_time } => {
+                    out.push(0);
+                    out.extend_from_slice(&delta_time.to_le_bytes());
+                }
+                ReplayEvent::MouseMotion { dx, dy } => {
+                    out.push(1);
+                    out.extend_from_slice(&dx.to_le_bytes());
+                    out.extend_from_slice(&dy.to_le_bytes());
+                }
+                ReplayEvent::KeyPressed(key) => out.extend_from_slice(&[2, key_index(key)]),
+                ReplayEvent::KeyReleased(key) => out.extend_from_slice(&[3, key_index(key)]),
+                ReplayEvent::MousePressed(btn) => out.extend_from_slice(&[4, button_index(btn)]),
+                ReplayEvent::MouseReleased(btn) => out.extend_from_slice(&[5, button_index(btn)]),
+            }
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, EngineError> {
+        let mut reader = ByteReader { bytes, pos: 0 };
+        if reader.take(4)? != MAGIC {
+            return Err(EngineError::ReplayFormatErr);
+        }
+        let version = reader.u8()?;
+        if version == 0 || version > VERSION {
+            return Err(EngineError::ReplayFormatErr);
+        }
+        let seed = reader.u64()?;
+        let sensitivity = reader.f32()?;
+        let move_speed = reader.f32()?;
+        let sphere_detail = reader.u32()?;
+        let start_on_first_hit = version >= 2 && reader.u8()? != 0;
+        let name_len = reader.u16()? as usize;
+        let scenario = String::from_utf8(reader.take(name_len)?.to_vec()).map_err(|_| EngineError::ReplayFormatErr)?;
+
+        let mut events = Vec::new();
+        while !reader.is_empty() {
+            let event = match reader.u8()? {
+                0 => ReplayEvent::Frame { delta_time: reader.f32()? },
+                1 => ReplayEvent::MouseMotion { dx: reader.f32()?, dy: reader.f32()? },
+                2 => ReplayEvent::KeyPressed(key_from_index(reader.u8()?)?),
+                3 => ReplayEvent::KeyReleased(key_from_index(reader.u8()?)?),
+                4 => ReplayEvent::MousePressed(button_from_index(reader.u8()?)?),
+                5 => ReplayEvent::MouseReleased(button_from_index(reader.u8()?)?),
+                _ => return Err(EngineError::ReplayFormatErr),
+            };
+            events.push(event);
+        }
+
+        Ok(Self { scenario, seed, sensitivity, move_speed, sphere_detail, start_on_first_hit, events })
+    }
+
+    pub fn save(&self) -> Result<PathBuf, EngineError> {
+        std::fs::create_dir_all(&*REPLAY_DIR)?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let file_name = format!("{}_{}.r3dr", self.scenario.to_lowercase().replace(' ', "_"), timestamp);
+        let path = REPLAY_DIR.join(file_name);
+
+        std::fs::write(&path, self.encode())?;
+        Ok(path)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, EngineError> {
+        Self::decode(&std::fs::read(path)?)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TargetState {
+    pub id: u32,
+    pub position: Vec3d,
+}
+
+#[derive(Clone, Debug)]
+pub struct FrameState {
+    pub time: f32, // Simulated run time at the end of the frame
+    pub position: Vec3d,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub flags: u8, // FLAG_SHOT and FLAG_HIT
+    pub targets: Vec<TargetState>,
+}
+impl FrameState {
+    pub fn capture(time: f32, camera: &Camera, targets: &TargetVec, flags: u8) -> Self {
+        Self {
+            time,
+            position: camera.position,
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+            flags,
+            targets: targets.vec.iter().map(|t| TargetState { id: t.id, position: t.position }).collect(),
+        }
+    }
+
+    pub fn look_dir(&self) -> Vec3d {
+        Camera::look_dir_for(self.yaw, self.pitch)
+    }
+}
+
+pub struct StateTrack {
+    pub scenario: String,
+    pub frames: Vec<FrameState>,
+}
+impl StateTrack {
+    pub fn new(scenario: &str) -> Self {
+        Self { scenario: scenario.to_string(), frames: Vec::with_capacity(1 << 14) }
+    }
+
+    pub fn frame_at(&self, time: f32) -> usize {
+        // Last frame that ended at or before the given time
+        self.frames.partition_point(|f| f.time <= time).saturating_sub(1)
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.frames.last().map_or(0.0, |f| f.time)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(8 + self.scenario.len() + self.frames.len() * 48);
+        out.extend_from_slice(STATE_MAGIC);
+        out.push(STATE_VERSION);
+        out.extend_from_slice(&(self.scenario.len() as u16).to_le_bytes());
+        out.extend_from_slice(self.scenario.as_bytes());
+
+        let push_vec = |out: &mut Vec<u8>, v: Vec3d| {
+            for c in [v.x, v.y, v.z] {
+                out.extend_from_slice(&c.to_le_bytes());
+            }
+        };
+        for frame in &self.frames {
+            out.extend_from_slice(&frame.time.to_le_bytes());
+            push_vec(&mut out, frame.position);
+            out.extend_from_slice(&frame.yaw.to_le_bytes());
+            out.extend_from_slice(&frame.pitch.to_le_bytes());
+            out.push(frame.flags);
+            out.push(frame.targets.len().min(u8::MAX as usize) as u8);
+            for target in frame.targets.iter().take(u8::MAX as usize) {
+                out.extend_from_slice(&target.id.to_le_bytes());
+                push_vec(&mut out, target.position);
+            }
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, EngineError> {
+        let mut reader = ByteReader { bytes, pos: 0 };
+        if reader.take(4)? != STATE_MAGIC || reader.u8()? != STATE_VERSION {
+            return Err(EngineError::ReplayFormatErr);
+        }
+        let name_len = reader.u16()? as usize;
+        let scenario = String::from_utf8(reader.take(name_len)?.to_vec()).map_err(|_| EngineError::ReplayFormatErr)?;
+
+        let mut frames = Vec::new();
+        while !reader.is_empty() {
+            let time = reader.f32()?;
+            let position = reader.vec3d()?;
+            let yaw = reader.f32()?;
+            let pitch = reader.f32()?;
+            let flags = reader.u8()?;
+            let count = reader.u8()?;
+            let mut targets = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                targets.push(TargetState { id: reader.u32()?, position: reader.vec3d()? });
+            }
+            frames.push(FrameState { time, position, yaw, pitch, flags, targets });
+        }
+        Ok(Self { scenario, frames })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), EngineError> {
+        std::fs::write(path, self.encode())?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, EngineError> {
+        Self::decode(&std::fs::read(path)?)
+    }
+}
+
+pub fn verify_replay(path: &Path) -> Result<(), EngineError> {
+/*
+    Feeds a replay through the simulation without a window and prints the reproduced result
+    next to the result stored in the run history (matched by scenario and seed).
+*/
+    let recording = Recording::load(path)?;
+    let mut scenario = find_scenario(&recording.scenario)?;
+
+    if recording.sphere_detail as usize != CONFIG.targets.sphere_detail {
+        eprintln!("Warning: Replay was recorded with sphere_detail = {}, config has {}. Hits may differ.",
+                  recording.sphere_detail, CONFIG.targets.sphere_detail);
+    }
+
+    let mut sim = Simulation::from_recording(&mut scenario, &recording);
+    for event in &recording.events {
+        sim.apply(&mut scenario, *event);
+    }
+    let stats = &mut sim.stats;
+    let score = stats.score();
+
+    println!("\nReplay of {} (seed {})", recording.scenario, recording.seed);
+    println!("Reproduced: {} hits, {} shots, {} kills, score {:.0}", stats.hits, stats.shots, stats.kills, score);
+
+    let history = History::load(&HISTORY_PATH)?;
+    match history.runs_for(&recording.scenario).find(|r| r.seed == recording.seed) {
+        Some(run) => {
+            println!("Recorded:   {} hits, {} shots, score {:.0}", run.hits, run.shots, run.score);
+            if run.hits == stats.hits && run.shots == stats.shots {
+                println!("Replay matches the recorded run.");
+            } else {
+                println!("Replay does NOT match the recorded run!");
+            }
+        }
+        None => println!("No run with this seed found in the run history."),
+    }
+    Ok(())
+}
+
+fn key_index(key: KeyCode) -> u8 {
+    RECORDED_KEYS.iter().position(|k| *k == key).unwrap_or(0) as u8
+}
+fn key_from_index(i: u8) -> Result<KeyCode, EngineError> {
+    RECORDED_KEYS.get(i as usize).copied().ok_or(EngineError::ReplayFormatErr)
+}
+fn button_index(btn: MouseButton) -> u8 {
+    RECORDED_BUTTONS.iter().position(|b| *b == btn).unwrap_or(0) as u8
+}
+fn button_from_index(i: u8) -> Result<MouseButton, EngineError> {
+    RECORDED_BUTTONS.get(i as usize).copied().ok_or(EngineError::ReplayFormatErr)
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+impl<'a> ByteReader<'a> {
+    fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+    fn take(&mut self, n: usize) -> Result<&'a [u8], EngineError> {
+        let slice = self.bytes.get(self.pos..self.pos + n).ok_or(EngineError::ReplayFormatErr)?;
+        self.pos += n;
+        Ok(slice)
+    }
+    fn u8(&mut self) -> Result<u8, EngineError> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16, EngineError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+    fn u32(&mut self) -> Result<u32, EngineError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn u64(&mut self) -> Result<u64, EngineError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+    fn f32(&mut self) -> Result<f32, EngineError> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn vec3d(&mut self) -> Result<Vec3d, EngineError> {
+        Ok(Vec3d::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+}
@@ -1,21 +1,24 @@
 use softbuffer::{Context, Surface};
 use std::{
     num::NonZeroU32,
-    time::{Instant},
     io::BufReader,
     fs::File
 };
 use winit::{
-    event::{DeviceEvent, ElementState, Event, MouseButton, WindowEvent},
+    event::{DeviceEvent, ElementState, Event, WindowEvent},
     keyboard::{KeyCode, PhysicalKey},
-    window::Fullscreen,
+    window::{Fullscreen, Window},
 };
 use rodio::{Decoder, Source};
 
-use crate::engine::{Statistic, draw_texture_optimized, GUI, GUI_TXT_PATH, EngineError, camera::Camera, core::{CONFIG, HIT_TARGET, Mat4x4, TriToRaster}, input::InputState, rendering::{draw_crosshair, render_triangles, room_proj_loop, target_proj_loop, tri_clip_xy, window}, scenario::{Scenario}, cli::play_again, TargetVec, draw_fps, Timer};
-use crate::engine::camera::FPS;
+use crate::engine::{Statistic, draw_texture_optimized, GUI, GUI_TXT_PATH, EngineError, core::{CONFIG, HIT_TARGET}, rendering::{draw_crosshair, window, SceneRenderer}, scenario::{Scenario}, cli::play_again, draw_fps, draw_render_stats, draw_countdown, Timer, Heatmap, HEATMAP_SIZE, RunRecord, History, HISTORY_PATH, simulation::Simulation, replay::STATE_EXTENSION, state::GameState};
+use crate::engine::{camera::{FrameLimiter, FPS}, capture::{save_screenshot, FrameCapture, SCREENSHOT_KEY}, clock::Clock};
 
-pub fn run(scenario: &mut Scenario) -> Result<(), EngineError>{
+pub fn run(scenario: &mut Scenario, clock: &dyn Clock) -> Result<(), EngineError>{
+/*
+    All real time (frame deltas, FPS counter, frame cap) is read from `clock`.
+    The simulation itself only ever sees the deltas, it keeps its own virtual time in fixed ticks.
+*/
     let (event_loop, window) = window::event_loop_setup()?;
     let mut window_size = window.inner_size();
 
@@ -23,11 +26,9 @@ pub fn run(scenario: &mut Scenario) -> Result<(), EngineError>{
     let context = Context::new(&window)?;
     let mut surface = Surface::new(&context, &window)?;
 
-    // Initialize custom structs
-    let mut camera = Camera::new(scenario.player_spawn);
-    let mut user_input = InputState::new();
-    let mut stats = Statistic::new();
-    let mut fps = FPS::init();
+    // Initialize custom structs (camera, input, targets and stats live in the simulation)
+    let mut sim = Simulation::new(scenario, rand::random(), true);
+    let mut fps = FPS::init(clock.now());
     let mut timer = Timer::new();
 
     let gui = match GUI::load_gui(GUI_TXT_PATH.to_str().unwrap()) {
@@ -40,17 +41,8 @@ pub fn run(scenario: &mut Scenario) -> Result<(), EngineError>{
         }
     };
 
-    // Initialize target vector
-    let mut target_vec = TargetVec::init(&scenario.t_settings);
-
-    // Pre-allocate Vectors
-    let mut tri_vec: Vec<TriToRaster> = Vec::with_capacity(1024);
-    let mut target_tri_vec: Vec<TriToRaster> = Vec::with_capacity(256);
-    let mut tri_clipped: Vec<TriToRaster> = Vec::with_capacity(4);
-
-    let mut proj_matrix = Mat4x4::projection(window_size.width as f32, window_size.height as f32);
-
-    let mut rng = rand::thread_rng();
+    // Pre-allocates the triangle buffers and holds the projection matrix
+    let mut renderer = SceneRenderer::new(window_size.width as usize, window_size.height as usize, &scenario.room_style);
 
     // SFX setup
     let mut stream_handle = rodio::OutputStreamBuilder::open_default_stream()?;
@@ -61,46 +53,20 @@ pub fn run(scenario: &mut Scenario) -> Result<(), EngineError>{
 
     let mut pixel_buffer: Vec<u32> = vec![0; window_size.width as usize * window_size.height as usize];
 
+    let mut state = GameState::countdown();
+    let mut limiter = FrameLimiter::new(CONFIG.display.fps_cap, clock.now());
+    let mut last_frame_time = clock.now();
+    let mut capture = FrameCapture::start(&scenario.name, CONFIG.capture.every_nth_frame);
+
     let _ = event_loop.run(|event, window_target| {
-        window_target.set_control_flow(winit::event_loop::ControlFlow::Poll);
-
-        match stats.scenario_starttime.elapsed() {
-            Ok(elapsed) => {
-                if elapsed > scenario.duration_secs && !stats.scenario_ended {
-                    // Hide window and prompt for replay
-                    window.set_fullscreen(None);
-                    window.set_minimized(true);
-                    stats.end_scenario();
-                    stats.print_stats(&scenario.name, fps.total_frame_count / stats.scenario_playtime());
-
-                    if play_again() {
-                        stats = Statistic::new();
-                        camera = Camera::new(scenario.player_spawn);
-                        target_vec = TargetVec::init(&scenario.t_settings);
-                        fps.total_frame_count = 0;
-                        window.set_minimized(false);
-                        window.set_fullscreen(Some(Fullscreen::Borderless(None)));
-                        window.focus_window();
-                    } else {
-                        window_target.exit();
-                        return;
-                    }
-                } else if !stats.scenario_ended {
-                    // This is used for timer display
-                    timer.seconds = (scenario.duration_secs - elapsed).as_secs() + 1;
-                    timer.update_mins();
-                }
-                
-            }
-            _ => {}
-        }
+
         match event {
             Event::WindowEvent { event, .. } => match event {
-                WindowEvent::CloseRequested => window_target.exit(),
+                WindowEvent::CloseRequested => state = GameState::Exiting,
 
                 WindowEvent::Resized(new_size) => {
                     window_size = new_size;
-                    proj_matrix = Mat4x4::projection(window_size.width as f32, window_size.height as f32);
+                    renderer.resize(window_size.width as usize, window_size.height as usize);
                     pixel_buffer.resize(window_size.width as usize * window_size.height as usize, 0);
                     surface
                         .resize(
@@ -115,184 +81,152 @@ pub fn run(scenario: &mut Scenario) -> Result<(), EngineError>{
                 } => {
                     if let PhysicalKey::Code(keycode) = key_event.physical_key {
                         match key_event.state {
-                            ElementState::Pressed => {
-                                if keycode == KeyCode::Escape {
-                                    window.set_fullscreen(None);
-                                    window.set_minimized(true);
-                                    /*
-                                    end scenario to be able to display total time played
-                                    print stats at the end
-                                */  stats.end_scenario();
-                                    stats.print_stats(&scenario.name,
-                                                      if stats.scenario_playtime() < 1 {0} else {fps.total_frame_count / stats.scenario_playtime()}
-                                    );
-
-                                    if play_again() {
-                                        stats = Statistic::new();
-                                        camera = Camera::new(scenario.player_spawn);
-                                        target_vec = TargetVec::init(&scenario.t_settings);
-                                        fps.total_frame_count = 00;
-                                        window.set_minimized(false);
-                                        window.set_fullscreen(Some(Fullscreen::Borderless(None)));
-                                        window.focus_window();
-                                    } else {
-                                        window_target.exit();
-                                        return;
-                                    }
+                            ElementState::Pressed => match (state, keycode) {
+                                (GameState::Countdown { .. } | GameState::Playing, KeyCode::Escape) => {
+                                    state = state.pause();
+                                    window::release_cursor(&window);
+                                    println!("Paused. Esc: resume | Enter: end run | R: restart");
                                 }
-                                if keycode == KeyCode::KeyR {
-                                    stats = Statistic::new();
-                                    camera = Camera::new(scenario.player_spawn);
-                                    target_vec = TargetVec::init(&scenario.t_settings);
-                                    fps.total_frame_count = 0;
-
+                                (GameState::Paused { .. }, KeyCode::Escape) => {
+                                    state = state.resume();
+                                    window::grab_cursor(&window);
+                                    last_frame_time = clock.now();
                                 }
-                                user_input.pressed_key(keycode);
-                            }
-                            ElementState::Released => {
-                                user_input.released_key(keycode);
-                            }
+                                (GameState::Paused { .. }, KeyCode::Enter) => state = GameState::Results,
+                                (GameState::Countdown { .. } | GameState::Playing | GameState::Paused { .. }, KeyCode::KeyR) => {
+                                    state = restart(&mut sim, &mut fps, &mut capture, scenario, clock);
+                                    window::grab_cursor(&window);
+                                    last_frame_time = clock.now();
+                                }
+                                // Held keys repeat, one press is one screenshot
+                                (_, SCREENSHOT_KEY) if key_event.repeat => {}
+                                (_, SCREENSHOT_KEY) => {
+                                    match save_screenshot(&pixel_buffer, window_size.width as usize, window_size.height as usize) {
+                                        Ok(path) => println!("Screenshot saved to {}", path.display()),
+                                        Err(e) => eprintln!("Warning: Failed to save screenshot: {}", e),
+                                    }
+                                }
+                                _ if state.captures_input() => sim.key_pressed(keycode),
+                                _ => {}
+                            },
+                            // Releases always go through, otherwise keys held while pausing would stay pressed
+                            ElementState::Released => sim.key_released(keycode),
                         }
                     }
                 }
 
-                WindowEvent::MouseInput { state, button, .. } => match state {
-                    ElementState::Pressed => {
-                        user_input.pressed_mouse(button);
-                    }
-                    ElementState::Released => {
-                        user_input.released_mouse(button);
-                    }
+                WindowEvent::MouseInput { state: button_state, button, .. } => match button_state {
+                    ElementState::Pressed if state == GameState::Playing => sim.mouse_pressed(button),
+                    ElementState::Pressed => {}
+                    ElementState::Released => sim.mouse_released(button),
                 },
 
                 WindowEvent::RedrawRequested => {
-                    fps.total_frame_count+=1; fps.interval_frame_count+=1;
-                    let now = Instant::now();
-                    let delta_time = now.duration_since(camera.last_frame_time).as_secs_f32();
-                    
-                    camera.last_frame_time = now;
-                    camera.update_look_dir();
-
-
-                    // User Keyboard Input
-                    let move_speed = CONFIG.input.move_speed * delta_time; 
-                    if scenario.allow_movement {user_input.handle_movement(&mut camera, move_speed)};
-                    user_input.check_fullscreen(&window);
-
-
-                    camera.update_view_matrix();
-
-
-                    tri_vec.clear();
-                    target_tri_vec.clear();
-
-                    room_proj_loop(
-                        &mut scenario.room,
-                        &mut tri_vec,
-                        &camera,
-                        &proj_matrix,
-                    );
-                /*  
-                    Logic required for registering hits in target_proj_loop(), in which we'll pass hit_target.
-                    gun_shot should be set to true (depending on scenario config) upon mouse click or hold (only if gun can shoot).
-                */  let mut hit_target = false;
-                    let mut gun_shot = false;
-                    if (!scenario.gun.automatic && user_input.mouse_buttons_just_pressed.contains(&MouseButton::Left)) || 
-                    (scenario.gun.automatic && user_input.mouse_buttons_pressed.contains(&MouseButton::Left) && scenario.gun.can_shoot()) {
-                        scenario.gun.shoot();
-                        gun_shot = true;
-                    }
+                    let now = clock.now();
+                    let delta_time = now.saturating_sub(last_frame_time).as_secs_f32();
+                    last_frame_time = now;
+                    fps.interval_frame_count+=1;
+
+                    sim.input.check_fullscreen(&window);
+
+                    match state {
+                        GameState::Countdown { .. } => {
+                        /*
+                            The scene is shown (and can be looked around in) but nothing moves yet.
+                            The first frames can take long while the window goes fullscreen, which must not eat the countdown.
+                        */  state = state.tick_countdown(delta_time.min(0.1));
+                            timer.seconds = scenario.duration_secs.as_secs();
+                            timer.update_mins();
+                        }
+                        GameState::Playing => {
+                            fps.total_frame_count+=1;
+                            sim.stats.record_render_frame();
+                        /*
+                            Movement, shooting, hit detection, respawning and stats all happen in the simulation,
+                            in fixed ticks that are independent of the frame rate.
+                            The runtime only plays sounds for hits and renders the resulting state.
+                        */  let outcome = sim.advance(scenario, delta_time);
+                            for _ in 0..outcome.targets_hit {
+                                stream_handle.mixer().add(src_hit_target.clone());
+                            }
 
-                /*  
-                    This is the main target loop.
-                    To avoid redundant looping, we handle both movement, rendering and hit detection in this loop
-                */  for target in &mut target_vec.vec {
-                        target.random_movement(camera.position, &mut rng, delta_time);
-                        let hit = target_proj_loop(target, &mut target_tri_vec, &camera,  gun_shot, &proj_matrix);
-                        if hit {
-                            stream_handle.mixer().add(src_hit_target.clone());
-                            hit_target = true;
-                            target.hp -= 1;
-                            if target.hp < 1 {
-                                target_vec.old = Some(target.position);
+                            let remaining = scenario.duration_secs.as_secs_f32() - sim.stats.elapsed;
+                            if remaining <= 0.0 {
+                                state = GameState::Results;
                             }
+                            // This is used for timer display
+                            timer.seconds = remaining.max(0.0).ceil() as u64;
+                            timer.update_mins();
                         }
-                        
+                        _ => {}
                     }
 
-                /* 
-                    Here, we sort the Target'sTriangles from furthest to closest (from camera), so that the triangles that are behind others
-                    get drawn first, to avoid translucency.
-                */  target_tri_vec.sort_unstable_by(|t1, t2| {
-                        t2.avg_z.partial_cmp(&t1.avg_z).unwrap_or(std::cmp::Ordering::Equal)
-                    });
-
-                /* 
-                    Room's Triangles are already in this vector, since the room does not have Triangles behind others.
-                    Other than that, we always want to render the room first and then the triangles.
-                */  tri_vec.append(&mut target_tri_vec);
-
+                /*
+                    Mouse input between two ticks is shown right away on a copy of the camera.
+                    The simulation's camera is only touched by ticks, so replays stay exact.
+                */  let mut view = sim.camera.clone();
+                    view.update_look_dir();
+                    view.update_view_matrix();
+                    let alpha = sim.interpolation_alpha();
 
-                    // Clearing the pixels of the last frame
-                    pixel_buffer.fill(0);
+                    let render_start = clock.now();
+                    renderer.render(&mut pixel_buffer, &mut scenario.room, &mut sim.targets.vec, &view, alpha);
+                    fps.render_time += clock.now().saturating_sub(render_start);
 
                     let width = window_size.width as usize;
                     let height = window_size.height as usize;
-                /* 
-                    We now iterate through the Triangle Vector, which contains the 2d Triangles of both the Targets and the Room.
-                    We first clipp the triangles at the screen edges and then use the fill function to draw them onto the screen.
-                */
-                    for tri2d in &mut tri_vec {
-                        tri_clipped.clear();
-                        tri_clip_xy(tri2d, &mut tri_clipped);
-                        render_triangles(&mut pixel_buffer, &tri_clipped, width, height);
-                    }
-                    
+
                     // Crosshair is drawn based on users settings in config.toml
                     draw_crosshair(&mut pixel_buffer, CONFIG.crosshair, width, height);
 
                     if let Some(gui) = &gui {
                         draw_texture_optimized(&mut pixel_buffer, width, height, &gui.logo, 0, 0);
 
-                        timer.draw_timer(&mut pixel_buffer, width, height, &gui);
+                        timer.draw_timer(&mut pixel_buffer, width, height, gui);
+
+                        if let GameState::Countdown { remaining } = state {
+                            draw_countdown(remaining.ceil() as u32, &mut pixel_buffer, width, height, &gui.digits_countdown);
+                        }
 
                         fps.update_str(now);
                         draw_fps(&fps, &mut pixel_buffer, width, height, &gui.digits_fps);
+                        draw_render_stats(&fps, renderer.supersampling, &mut pixel_buffer, width, height, &gui.digits_fps);
                     }
-                /*
-                    Remove Targets with hp <= 0 and spawn in a new one to keep the target count consistent
-                */  target_vec.vec.retain(|x| x.hp > 0);
-                    if target_vec.vec.len() < scenario.t_settings.count {
-                        target_vec.add_target();
-                    }
-                
-                /*
-                    Simply updates stats 
-                */  if gun_shot && hit_target {
-                        stats.add_hit();
-                    } else if gun_shot {
-                        stats.add_shot();
-                    }
-
                     let mut buffer = surface.buffer_mut().unwrap();
                     buffer.copy_from_slice(&pixel_buffer);
                     buffer.present().unwrap();
-                    
-                /* 
-                    This should be done every frame to prevent single presses being detected as holding down 
-                */  user_input.clear_just_pressed();
+
+                    // Frames while paused or on the results are not part of the run
+                    if state.captures_input() {
+                        if let Some(capture) = &mut capture {
+                            capture.frame(&pixel_buffer, width, height);
+                        }
+                    }
                 }
                 _ => (),
             },
             Event::DeviceEvent {
                 event: DeviceEvent::MouseMotion { delta },
                 ..
-            } => {
+            } if state.captures_input() => {
                 let (delta_x, delta_y) = delta;
-                camera.update_yaw_pitch(delta_x, delta_y);
+                sim.mouse_motion(delta_x, delta_y);
             }
             Event::AboutToWait => {
-                window.request_redraw();
+                if state == GameState::Results {
+                    state = show_results(&window, &mut sim, &mut fps, &mut capture, scenario, clock);
+                    // The results prompt blocks, that time is not part of the next frame
+                    last_frame_time = clock.now();
+                }
+                if state == GameState::Exiting {
+                    window_target.exit();
+                    return;
+                }
+                let now = clock.now();
+                if limiter.ready(now) {
+                    window.request_redraw();
+                }
+                window_target.set_control_flow(limiter.control_flow(now));
             }
             _ => {}
         };
@@ -301,3 +235,78 @@ pub fn run(scenario: &mut Scenario) -> Result<(), EngineError>{
 
     Ok(())
 }
+
+fn restart(sim: &mut Simulation, fps: &mut FPS, capture: &mut Option<FrameCapture>, scenario: &mut Scenario, clock: &dyn Clock) -> GameState {
+/*
+    Every way into a new run goes through here, so camera, targets, stats, the
+    FPS counter and the frame capture are always reset together (the camera and targets live in the simulation).
+*/
+    *sim = Simulation::new(scenario, rand::random(), true);
+    *fps = FPS::init(clock.now());
+    // Each run gets its own numbered sequence, the previous one is finished first
+    *capture = None;
+    *capture = FrameCapture::start(&scenario.name, CONFIG.capture.every_nth_frame);
+    GameState::countdown()
+}
+
+fn show_results(window: &Window, sim: &mut Simulation, fps: &mut FPS, capture: &mut Option<FrameCapture>, scenario: &mut Scenario, clock: &dyn Clock) -> GameState {
+    // Hide window and prompt for replay, the captured frames are written out before the results
+    *capture = None;
+    window.set_fullscreen(None);
+    window.set_minimized(true);
+    window::release_cursor(window);
+    report_run(sim, scenario, fps.total_frame_count);
+
+    if play_again() {
+        window.set_minimized(false);
+        window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+        window.focus_window();
+        window::grab_cursor(window);
+        restart(sim, fps, capture, scenario, clock)
+    } else {
+        GameState::Exiting
+    }
+}
+
+fn report_run(sim: &mut Simulation, scenario: &Scenario, total_frame_count: u32) {
+/*
+    Everything that happens once a run is over: results in the terminal,
+    the hit heatmap, the input replay and an entry in the persistent run history.
+*/
+    let stats = &mut sim.stats;
+    let avg_fps = if stats.scenario_playtime() < 1 {0} else {total_frame_count / stats.scenario_playtime()};
+    let mut history = History::load(&HISTORY_PATH).unwrap_or_else(|e| {
+        eprintln!("Warning: Failed to load run history: {}", e);
+        History::empty(&HISTORY_PATH)
+    });
+    stats.print_stats(&scenario.name, avg_fps, &history);
+    export_heatmap(stats, &scenario.name);
+
+    let mut record = RunRecord::new(scenario, stats, avg_fps);
+    if let Some(recording) = &sim.recording {
+        match recording.save() {
+            Ok(path) => {
+                if let Some(states) = &sim.states {
+                    if let Err(e) = states.save(&path.with_extension(STATE_EXTENSION)) {
+                        eprintln!("Warning: Failed to save replay states: {}", e);
+                    }
+                }
+                record.replay = Some(path.display().to_string());
+            }
+            Err(e) => eprintln!("Warning: Failed to save replay: {}", e),
+        }
+    }
+    if let Err(e) = history.append(record) {
+        eprintln!("Warning: Failed to save run history: {}", e);
+    }
+}
+
+fn export_heatmap(stats: &Statistic, scenario_name: &str) {
+    if stats.offsets.is_empty() {
+        return;
+    }
+    match Heatmap::from_offsets(&stats.offsets, HEATMAP_SIZE).export_png(scenario_name) {
+        Ok(path) => println!("Hit heatmap saved to {}", path.display()),
+        Err(e) => eprintln!("Warning: Failed to export hit heatmap: {}", e),
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use crate::engine::scenario::{load_all_scenarios, AimingType, History, RunRecord, RECENT_RUNS};
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+pub struct ScenarioTrend {
+    pub scenario: String,
+    pub aiming_type: Option<AimingType>, // None for scenarios that no longer exist
+    pub runs: usize,
+    pub best: f32,
+    pub worst: f32,
+    pub overall_avg: f32,
+    pub rolling_avg: f32, // Average of the last RECENT_RUNS runs
+    pub change_7d: Option<f32>, // % change of the average score in the window vs. everything before it
+    pub change_30d: Option<f32>,
+}
+
+pub struct CategoryTrend {
+    pub aiming_type: AimingType,
+    pub relative_form: f32, // Recent average relative to the all-time average, averaged over the category's scenarios
+}
+
+pub fn scenario_trends(history: &History, now: u64) -> Vec<ScenarioTrend> {
+    let aiming_types: HashMap<String, AimingType> = load_all_scenarios()
+        .into_iter()
+        .map(|s| (s.name, s.aiming_type))
+        .collect();
+
+    // Keep the order in which scenarios were first played
+    let mut names: Vec<&str> = Vec::new();
+    for run in &history.runs {
+        if !names.contains(&run.scenario.as_str()) {
+            names.push(&run.scenario);
+        }
+    }
+
+    names.into_iter().map(|name| {
+        // Trends follow how the scenario is scored now, runs scored another way are on a different scale
+        let kind = history.runs_for(name).last().map(|r| r.score_kind).unwrap_or_default();
+        let runs: Vec<&RunRecord> = history.scored_runs(name, kind).collect();
+        let scores: Vec<f32> = runs.iter().map(|r| r.score).collect();
+
+        ScenarioTrend {
+            scenario: name.to_string(),
+            aiming_type: aiming_types.get(name).copied(),
+            runs: runs.len(),
+            best: scores.iter().cloned().fold(f32::MIN, f32::max),
+            worst: scores.iter().cloned().fold(f32::MAX, f32::min),
+            overall_avg: mean(&scores).unwrap_or(0.0),
+            rolling_avg: mean(&scores[scores.len().saturating_sub(RECENT_RUNS)..]).unwrap_or(0.0),
+            change_7d: window_change(&runs, now, 7),
+            change_30d: window_change(&runs, now, 30),
+        }
+    }).collect()
+}
+
+pub fn category_trends(trends: &[ScenarioTrend]) -> Vec<CategoryTrend> {
+    let mut categories: Vec<(AimingType, Vec<f32>)> = Vec::new();
+    for trend in trends {
+        let Some(aiming_type) = trend.aiming_type else { continue };
+        if trend.overall_avg <= 0.0 {
+            continue;
+        }
+        let form = trend.rolling_avg / trend.overall_avg;
+        match categories.iter_mut().find(|(t, _)| *t == aiming_type) {
+            Some((_, forms)) => forms.push(form),
+            None => categories.push((aiming_type, vec![form])),
+        }
+    }
+    categories.into_iter()
+        .map(|(aiming_type, forms)| CategoryTrend { aiming_type, relative_form: mean(&forms).unwrap_or(1.0) })
+        .collect()
+}
+
+pub fn weakest_category(categories: &[CategoryTrend]) -> Option<&CategoryTrend> {
+    categories.iter()
+        .min_by(|a, b| a.relative_form.partial_cmp(&b.relative_form).unwrap_or(std::cmp::Ordering::Equal))
+}
+
+fn window_change(runs: &[&RunRecord], now: u64, days: u64) -> Option<f32> {
+    let start = now.saturating_sub(days * DAY_SECS);
+    let (recent, before): (Vec<&&RunRecord>, Vec<&&RunRecord>) = runs.iter().partition(|r| r.timestamp >= start);
+
+    let recent_avg = mean(&recent.iter().map(|r| r.score).collect::<Vec<f32>>())?;
+    let before_avg = mean(&before.iter().map(|r| r.score).collect::<Vec<f32>>())?;
+    if before_avg <= 0.0 {
+        return None;
+    }
+    Some((recent_avg - before_avg) / before_avg * 100.0)
+}
+
+fn mean(values: &[f32]) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    Some(values.iter().sum::<f32>() / values.len() as f32)
+}
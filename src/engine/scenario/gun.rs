@@ -1,11 +1,9 @@
 #![allow(unused)]
 
-use std::{ops::Sub, time::{Duration, Instant}};
-
 pub struct Gun {
     pub automatic:bool,
     pub fire_rate_s:f32,
-    pub last_shot:Instant,
+    pub last_shot:f32, // Run time of the last shot in seconds
 }
 impl Gun {
     
@@ -13,7 +11,7 @@ impl Gun {
         Self {
             automatic: false,
             fire_rate_s: 0.01,
-            last_shot:Instant::now()
+            last_shot:f32::NEG_INFINITY
         }
     }
 
@@ -21,15 +19,19 @@ impl Gun {
         Self {
             automatic: true,
             fire_rate_s: 0.05,
-            last_shot:Instant::now()
+            last_shot:f32::NEG_INFINITY
         }
     }
 
-    pub fn can_shoot(&self) -> bool {
-        self.last_shot.elapsed().as_secs_f32() >= self.fire_rate_s
+    pub fn can_shoot(&self, now:f32) -> bool {
+        now - self.last_shot >= self.fire_rate_s
+    }
+
+    pub fn shoot(&mut self, now:f32) {
+        self.last_shot = now;
     }
 
-    pub fn shoot(&mut self) {
-        self.last_shot = Instant::now();
+    pub fn reset(&mut self) {
+        self.last_shot = f32::NEG_INFINITY;
     }
 }
@@ -0,0 +1,97 @@
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::engine::{EngineError, Texture, HEATMAP_DIR};
+use crate::engine::scenario::ShotOffset;
+
+pub const HEATMAP_SIZE: usize = 256;
+const SPLAT_RADIUS: i32 = 4;
+
+pub struct Heatmap {
+    pub size: usize,
+    pub range_deg: f32, // Half the width of the plotted area in degrees
+    pub bins: Vec<f32>,
+}
+impl Heatmap {
+    pub fn from_offsets(offsets: &[ShotOffset], size: usize) -> Self {
+    /*
+        The plotted range is picked from the 95th percentile of all offsets,
+        so single wild misses don't squash every other shot into the centre pixel.
+    */
+        let mut extents: Vec<f32> = offsets.iter().map(|o| o.angular.0.abs().max(o.angular.1.abs())).collect();
+        extents.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let range_deg = extents
+            .get((extents.len() * 95 / 100).min(extents.len().saturating_sub(1)))
+            .map_or(1.0, |e| (e * 1.1).ceil().max(1.0));
+
+        let mut heatmap = Self { size, range_deg, bins: vec![0.0; size * size] };
+        for offset in offsets {
+            heatmap.splat(offset.angular);
+        }
+        heatmap
+    }
+
+    fn splat(&mut self, angular: (f32, f32)) {
+        // Horizontal offset grows to the right, vertical offset grows upwards (image y is flipped)
+        let half = self.size as f32 * 0.5;
+        let cx = (half + angular.0 / self.range_deg * half) as i32;
+        let cy = (half - angular.1 / self.range_deg * half) as i32;
+
+        let sigma_sq = (SPLAT_RADIUS as f32 * 0.5).powi(2);
+        for dy in -SPLAT_RADIUS..=SPLAT_RADIUS {
+            for dx in -SPLAT_RADIUS..=SPLAT_RADIUS {
+                let x = cx + dx;
+                let y = cy + dy;
+                if x < 0 || y < 0 || x >= self.size as i32 || y >= self.size as i32 {
+                    continue;
+                }
+                let weight = (-((dx * dx + dy * dy) as f32) / (2.0 * sigma_sq)).exp();
+                self.bins[y as usize * self.size + x as usize] += weight;
+            }
+        }
+    }
+
+    pub fn to_texture(&self) -> Texture {
+        let max = self.bins.iter().cloned().fold(0.0, f32::max);
+        let centre = self.size / 2;
+
+        let data = self.bins.iter().enumerate().map(|(i, &v)| {
+            let (x, y) = (i % self.size, i / self.size);
+            if v <= 0.0 && (x == centre || y == centre) {
+                // Axes through the target centre
+                0xFF404040
+            } else {
+                heat_color(if max > 0.0 { v / max } else { 0.0 })
+            }
+        }).collect();
+
+        Texture::from_argb(self.size, self.size, data)
+    }
+
+    pub fn export_png(&self, scenario_name: &str) -> Result<PathBuf, EngineError> {
+        std::fs::create_dir_all(&*HEATMAP_DIR)?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let file_name = format!("{}_{}.png", scenario_name.to_lowercase().replace(' ', "_"), timestamp);
+        let path = HEATMAP_DIR.join(file_name);
+
+        self.to_texture().save_to_png(&path)?;
+        Ok(path)
+    }
+}
+
+fn heat_color(t: f32) -> u32 {
+    // black -> blue -> red -> yellow -> white
+    const STOPS: [[f32; 3]; 5] = [
+        [0.0, 0.0, 0.0],
+        [0.0, 0.0, 200.0],
+        [220.0, 0.0, 0.0],
+        [255.0, 220.0, 0.0],
+        [255.0, 255.0, 255.0],
+    ];
+    let scaled = t.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let i = (scaled as usize).min(STOPS.len() - 2);
+    let f = scaled - i as f32;
+
+    let lerp = |c: usize| (STOPS[i][c] + (STOPS[i + 1][c] - STOPS[i][c]) * f) as u32;
+    0xFF000000 | (lerp(0) << 16) | (lerp(1) << 8) | lerp(2)
+}
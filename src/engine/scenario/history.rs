@@ -0,0 +1,148 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{EngineError, FlickMetrics, Scenario, ScoreKind, Statistic, Timeline, TrackingMetrics, CONFIG};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+/*
+    One finished run, stored as a single line of JSON in the history file.
+    New fields must be optional (#[serde(default)]) so older history files keep loading.
+*/
+    pub timestamp: u64, // Unix seconds
+    pub scenario: String,
+    pub seed: u64,
+    pub duration_secs: u32,
+    pub hits: u32,
+    pub shots: u32,
+    pub accuracy: f32,
+    pub score: f32,
+    #[serde(default)]
+    pub score_kind: ScoreKind, // Only scores of the same kind are compared
+    pub avg_fps: u32,
+    pub config: ConfigSnapshot,
+    #[serde(default)]
+    pub timeline: Timeline,
+    #[serde(default)]
+    pub replay: Option<String>, // Path of the input replay, used to verify the run
+    #[serde(default)]
+    pub flicks: Option<FlickMetrics>,
+    #[serde(default)]
+    pub tracking: Option<TrackingMetrics>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigSnapshot {
+    pub sensitivity: f32,
+    pub fov: f32,
+    pub display_width: usize,
+    pub display_height: usize,
+}
+impl ConfigSnapshot {
+    pub fn current() -> Self {
+        Self {
+            sensitivity: CONFIG.input.sensitivity,
+            fov: CONFIG.camera.fov,
+            display_width: CONFIG.display.width,
+            display_height: CONFIG.display.height,
+        }
+    }
+}
+
+impl RunRecord {
+    pub fn new(scenario: &Scenario, stats: &mut Statistic, avg_fps: u32) -> Self {
+        Self {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+            scenario: scenario.name.clone(),
+            seed: stats.seed,
+            duration_secs: stats.scenario_playtime(),
+            hits: stats.hits,
+            shots: stats.shots,
+            accuracy: stats.get_accuracy_p(),
+            score: stats.score(),
+            score_kind: stats.score_kind(),
+            avg_fps,
+            config: ConfigSnapshot::current(),
+            timeline: stats.timeline.clone(),
+            replay: None,
+            flicks: stats.mouse_path.metrics(stats.kills),
+            tracking: stats.tracking.as_ref().and_then(|t| t.metrics()),
+        }
+    }
+}
+
+pub struct History {
+/*
+    All previous runs loaded from the history file.
+    Lines that fail to parse (e.g. a run cut off mid-write) are skipped instead of failing the whole load.
+*/
+    pub path: PathBuf,
+    pub runs: Vec<RunRecord>,
+}
+impl History {
+    pub fn empty(path: &Path) -> Self {
+        Self { path: path.to_path_buf(), runs: Vec::new() }
+    }
+    pub fn load(path: &Path) -> Result<Self, EngineError> {
+        let mut runs = Vec::new();
+        match File::open(path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    let line = line?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    if let Ok(record) = serde_json::from_str::<RunRecord>(&line) {
+                        runs.push(record);
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(Self { path: path.to_path_buf(), runs })
+    }
+    pub fn append(&mut self, record: RunRecord) -> Result<(), EngineError> {
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        let line = serde_json::to_string(&record).map_err(|_| EngineError::HistoryErr)?;
+        writeln!(file, "{}", line)?;
+        self.runs.push(record);
+        Ok(())
+    }
+    pub fn runs_for<'a>(&'a self, scenario: &'a str) -> impl Iterator<Item = &'a RunRecord> + 'a {
+        self.runs.iter().filter(move |r| r.scenario == scenario)
+    }
+    pub fn scored_runs<'a>(&'a self, scenario: &'a str, kind: ScoreKind) -> impl Iterator<Item = &'a RunRecord> + 'a {
+        self.runs_for(scenario).filter(move |r| r.score_kind == kind)
+    }
+    pub fn personal_best<'a>(&'a self, scenario: &'a str, kind: ScoreKind) -> Option<&'a RunRecord> {
+        self.scored_runs(scenario, kind)
+            .max_by(|a, b| a.score.partial_cmp(&b.score).unwrap_or(std::cmp::Ordering::Equal))
+    }
+    pub fn average_last(&self, scenario: &str, kind: ScoreKind, n: usize) -> Option<f32> {
+        // Runs are stored in the order they were played, so the last n are the most recent
+        let scores: Vec<f32> = self.scored_runs(scenario, kind).map(|r| r.score).collect();
+        let recent = &scores[scores.len().saturating_sub(n)..];
+        if recent.is_empty() {
+            return None;
+        }
+        Some(recent.iter().sum::<f32>() / recent.len() as f32)
+    }
+    pub fn percentile_rank(&self, scenario: &str, kind: ScoreKind, score: f32) -> Option<f32> {
+        // Share of previous runs that this score beats or equals
+        let (mut below, mut total) = (0, 0);
+        for run in self.scored_runs(scenario, kind) {
+            total += 1;
+            if run.score <= score {
+                below += 1;
+            }
+        }
+        if total == 0 {
+            return None;
+        }
+        Some(below as f32 / total as f32 * 100.0)
+    }
+}
@@ -1,11 +1,24 @@
+#[allow(clippy::module_inception)]
 pub mod scenario;
 pub mod scene;
 pub mod target;
 pub mod stats;
 pub mod gun;
+pub mod heatmap;
+pub mod history;
+pub mod analytics;
+pub mod timeline;
+pub mod mouse_path;
+pub mod tracking;
 
 pub use target::*;
 pub use scenario::*;
 pub use scene::*;
 pub use stats::*;
-pub use gun::*;
\ No newline at end of file
+pub use gun::*;
+pub use heatmap::*;
+pub use history::*;
+pub use analytics::*;
+pub use timeline::*;
+pub use mouse_path::*;
+pub use tracking::*;
\ No newline at end of file
@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+
+use crate::engine::core::Vec3d;
+
+const MIN_FLICK_DEG: f32 = 2.0; // Shorter aim paths are adjustments on the same spot, not flicks
+const REST_DEG_PER_SEC: f32 = 5.0; // Below this speed the mouse counts as resting between two sub-movements
+const JITTER_WINDOW: f32 = 0.15; // Seconds before a click in which remaining movement counts as micro-adjusting
+
+#[derive(Clone, Copy)]
+struct PathPoint {
+    delta_time: f32,
+    yaw: f32, // Degrees, accumulated from per-frame deltas so it never wraps around
+    pitch: f32,
+}
+
+#[derive(Default)]
+pub struct MousePath {
+/*
+    Follows the crosshair in yaw/pitch space between two shots. Every shot closes the current path (a flick),
+    which is then measured against the direction of the target the shot was aimed at.
+*/
+    last_angles: Option<(f32, f32)>, // Camera yaw/pitch (radians) of the previous frame
+    path: Vec<PathPoint>,
+    flicks: u32,
+    overshoots: u32,
+    overshoot_sum: f32,
+    undershoots: u32,
+    undershoot_sum: f32,
+    corrections: u32,
+    efficiency_sum: f32,
+    jitter_sq_sum: f32,
+    jitter_time: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct FlickMetrics {
+    pub flicks: u32,
+    pub overshoot_rate: f32, // % of flicks that went past the target centre
+    pub mean_overshoot_deg: f32,
+    pub undershoot_rate: f32, // % of flicks clicked before ever reaching the target centre
+    pub mean_undershoot_deg: f32,
+    pub corrections_per_kill: f32,
+    pub path_efficiency: f32, // Straight-line angle / travelled angle in %
+    pub jitter_deg_s: f32, // RMS crosshair speed right before clicking
+}
+
+impl MousePath {
+    pub fn record_frame(&mut self, delta_time: f32, yaw: f32, pitch: f32) {
+        let (d_yaw, d_pitch) = match self.last_angles {
+            Some((last_yaw, last_pitch)) => (wrap_angle(yaw - last_yaw).to_degrees(), (pitch - last_pitch).to_degrees()),
+            None => (0.0, 0.0),
+        };
+        self.last_angles = Some((yaw, pitch));
+
+        let (prev_yaw, prev_pitch) = self.path.last().map_or((0.0, 0.0), |p| (p.yaw, p.pitch));
+        self.path.push(PathPoint { delta_time, yaw: prev_yaw + d_yaw, pitch: prev_pitch + d_pitch });
+    }
+
+    pub fn end_flick(&mut self, target_dir: Option<Vec3d>) {
+    /*
+        Called for every shot with the direction from the camera to the target the shot was meant for.
+        All distances are measured along the line from the flick's start to the target:
+        going further than the target is overshoot, clicking before reaching it is undershoot.
+    */
+        let (Some(start), Some(click), Some((yaw, pitch))) = (self.path.first().copied(), self.path.last().copied(), self.last_angles) else {
+            return;
+        };
+        self.add_jitter();
+
+        if let Some(dir) = target_dir {
+            // Target position in the same accumulated space as the path
+            let target_yaw = click.yaw + wrap_angle(dir.x.atan2(dir.z) - yaw).to_degrees();
+            let target_pitch = click.pitch + (dir.y.clamp(-1.0, 1.0).asin() - pitch).to_degrees();
+            let (to_x, to_y) = (target_yaw - start.yaw, target_pitch - start.pitch);
+            let distance = to_x.hypot(to_y);
+
+            if distance >= MIN_FLICK_DEG {
+                self.measure_flick(start, (to_x / distance, to_y / distance), distance);
+            }
+        }
+
+        // The next flick starts where this one was clicked
+        self.path.clear();
+        self.path.push(click);
+    }
+
+    fn measure_flick(&mut self, start: PathPoint, axis: (f32, f32), distance: f32) {
+        let progress = |p: &PathPoint| (p.yaw - start.yaw) * axis.0 + (p.pitch - start.pitch) * axis.1;
+        let peak = self.path.iter().map(progress).fold(f32::MIN, f32::max);
+
+        self.flicks += 1;
+        if peak > distance {
+            self.overshoots += 1;
+            self.overshoot_sum += peak - distance;
+        } else if let Some(click) = self.path.last() {
+            self.undershoots += 1;
+            self.undershoot_sum += distance - progress(click);
+        }
+
+        let mut travelled = 0.0;
+        let mut sub_movements: u32 = 0;
+        let mut prev_step: Option<(f32, f32)> = None;
+        for pair in self.path.windows(2) {
+            let step = (pair[1].yaw - pair[0].yaw, pair[1].pitch - pair[0].pitch);
+            let length = step.0.hypot(step.1);
+            travelled += length;
+
+            // A new sub-movement starts after resting or when the direction flips
+            let moving = pair[1].delta_time > 0.0 && length / pair[1].delta_time > REST_DEG_PER_SEC;
+            if moving {
+                let reversed = prev_step.is_some_and(|p| p.0 * step.0 + p.1 * step.1 < 0.0);
+                if prev_step.is_none() || reversed {
+                    sub_movements += 1;
+                }
+                prev_step = Some(step);
+            } else {
+                prev_step = None;
+            }
+        }
+        self.corrections += sub_movements.saturating_sub(1);
+
+        if let Some(click) = self.path.last() {
+            let straight = (click.yaw - start.yaw).hypot(click.pitch - start.pitch);
+            if travelled > 0.0 {
+                self.efficiency_sum += (straight / travelled).min(1.0);
+            }
+        }
+    }
+
+    fn add_jitter(&mut self) {
+        let mut window = 0.0;
+        for pair in self.path.windows(2).rev() {
+            if window >= JITTER_WINDOW || pair[1].delta_time <= 0.0 {
+                break;
+            }
+            let speed = (pair[1].yaw - pair[0].yaw).hypot(pair[1].pitch - pair[0].pitch) / pair[1].delta_time;
+            self.jitter_sq_sum += speed * speed * pair[1].delta_time;
+            self.jitter_time += pair[1].delta_time;
+            window += pair[1].delta_time;
+        }
+    }
+
+    pub fn metrics(&self, kills: u32) -> Option<FlickMetrics> {
+        if self.flicks == 0 {
+            return None;
+        }
+        let flicks = self.flicks as f32;
+        let mean = |sum: f32, count: u32| if count > 0 { sum / count as f32 } else { 0.0 };
+        Some(FlickMetrics {
+            flicks: self.flicks,
+            overshoot_rate: self.overshoots as f32 / flicks * 100.0,
+            mean_overshoot_deg: mean(self.overshoot_sum, self.overshoots),
+            undershoot_rate: self.undershoots as f32 / flicks * 100.0,
+            mean_undershoot_deg: mean(self.undershoot_sum, self.undershoots),
+            corrections_per_kill: mean(self.corrections as f32, kills),
+            path_efficiency: self.efficiency_sum / flicks * 100.0,
+            jitter_deg_s: if self.jitter_time > 0.0 { (self.jitter_sq_sum / self.jitter_time).sqrt() } else { 0.0 },
+        })
+    }
+}
+
+impl FlickMetrics {
+    pub fn print(&self) {
+        println!("Flicks: {} ({:.0}% overshot by {:.2}° on average, {:.0}% clicked {:.2}° short)",
+                 self.flicks, self.overshoot_rate, self.mean_overshoot_deg, self.undershoot_rate, self.mean_undershoot_deg);
+        println!("Corrections per Kill: {:.2}", self.corrections_per_kill);
+        println!("Path Efficiency: {:.0}%", self.path_efficiency);
+        println!("Micro-adjust Jitter: {:.1}°/s", self.jitter_deg_s);
+    }
+}
+
+fn wrap_angle(angle: f32) -> f32 {
+    // Maps a radian angle difference into [-PI, PI], yaw wraps at 2 PI in Camera::update_look_dir
+    let two_pi = 2.0 * std::f32::consts::PI;
+    let wrapped = angle.rem_euclid(two_pi);
+    if wrapped > std::f32::consts::PI { wrapped - two_pi } else { wrapped }
+}
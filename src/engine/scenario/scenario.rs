@@ -1,6 +1,6 @@
 use std::time::Duration;
 
-use crate::engine::{Gun, Mesh, RoomType, Vec3d, create_room, scenario::{MovingTarget, TargetShape}, TargetSettings};
+use crate::engine::{EngineError, Gun, Mesh, RoomType, RoomStyle, Vec3d, create_room, scenario::{MovingTarget, TargetShape}, TargetSettings};
 use crate::engine::color::Colors;
 
 pub struct Scenario {
@@ -19,10 +19,12 @@ pub struct Scenario {
 
     //room (centered around origin) 
     pub room: Mesh,
+    pub room_style: RoomStyle,
 
     //target properties
     pub t_settings: TargetSettings
 }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AimingType {
     StaticClicking,
     DynamicClicking,
@@ -31,6 +33,22 @@ pub enum AimingType {
     SpeedSwitching,
     EvasiveSwitching
 }
+impl AimingType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AimingType::StaticClicking => "Static Clicking",
+            AimingType::DynamicClicking => "Dynamic Clicking",
+            AimingType::ReactiveTracking => "Reactive Tracking",
+            AimingType::PreciseTracking => "Precise Tracking",
+            AimingType::SpeedSwitching => "Speed Switching",
+            AimingType::EvasiveSwitching => "Evasive Switching",
+        }
+    }
+    pub fn is_tracking(&self) -> bool {
+        // Tracking is judged by time on target, not by clicks
+        matches!(self, AimingType::ReactiveTracking | AimingType::PreciseTracking)
+    }
+}
 impl Scenario {
     fn jumbo_tf() -> Self {
         Scenario {
@@ -39,6 +57,7 @@ impl Scenario {
             duration_secs: Duration::from_secs(30),
             player_spawn: Vec3d::new(0.0, 1.0, -4.0),
             room: create_room(RoomType::Cube, 5.0),
+            room_style: RoomStyle::grid(1.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Block,
                 spawn: (
@@ -61,6 +80,7 @@ impl Scenario {
             duration_secs: Duration::from_secs(30),
             player_spawn: Vec3d::new(0.0, 1.0, -4.0),
             room: create_room(RoomType::Cube, 5.0),
+            room_style: RoomStyle::grid(1.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Square,
                 spawn: (
@@ -83,6 +103,7 @@ impl Scenario {
             duration_secs: Duration::from_secs(30),
             player_spawn: Vec3d::new(0.0, 1.0, -4.0),
             room: create_room(RoomType::Cube, 5.0),
+            room_style: RoomStyle::grid(1.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Square,
                 spawn: (
@@ -105,6 +126,7 @@ impl Scenario {
             duration_secs: Duration::from_secs(60),
             player_spawn: Vec3d::new(0.0, 1.0, -4.0),
             room: create_room(RoomType::Cube, 5.0),
+            room_style: RoomStyle::grid(1.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Sphere,
                 spawn: (
@@ -127,6 +149,7 @@ impl Scenario {
             duration_secs: Duration::from_secs(60),
             player_spawn: Vec3d::new(0.0, 1.0, -4.0),
             room: create_room(RoomType::Cube, 5.0),
+            room_style: RoomStyle::grid(1.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Sphere,
                 spawn: (
@@ -149,6 +172,7 @@ impl Scenario {
             duration_secs: Duration::from_secs(60),
             player_spawn: Vec3d::new(0.0, 1.0, -4.0),
             room: create_room(RoomType::Cube, 5.0),
+            room_style: RoomStyle::grid(1.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Sphere,
                 spawn: (
@@ -171,6 +195,7 @@ impl Scenario {
             duration_secs: Duration::from_secs(60),
             player_spawn: Vec3d::new(0.0, 1.0, 0.0),
             room: create_room(RoomType::Cone, 20.0),
+            room_style: RoomStyle::grid(4.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Sphere,
                 spawn: (
@@ -193,6 +218,7 @@ impl Scenario {
             duration_secs: Duration::from_secs(60),
             player_spawn: Vec3d::new(0.0, 1.0, -2.0),
             room: create_room(RoomType::Cone, 20.0),
+            room_style: RoomStyle::grid(4.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Sphere,
                 spawn: (
@@ -215,6 +241,7 @@ impl Scenario {
             aiming_type: AimingType::EvasiveSwitching,
             player_spawn: Vec3d::new(0.0, -5.0, -2.0),
             room: create_room(RoomType::Cone, 10.0),
+            room_style: RoomStyle::grid(4.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Sphere,
                 spawn: (
@@ -251,6 +278,7 @@ impl Scenario {
             duration_secs: Duration::from_secs(90),
             player_spawn: Vec3d::new(0.0, 1.0, -4.0),
             room: create_room(RoomType::Cube, 5.0),
+            room_style: RoomStyle::grid(1.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Sphere,
                 spawn: (
@@ -287,6 +315,7 @@ impl Scenario {
             duration_secs: Duration::from_secs(90),
             player_spawn: Vec3d::new(0.0, 1.0, -4.0),
             room: create_room(RoomType::Cube, 5.0),
+            room_style: RoomStyle::grid(1.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Sphere,
                 spawn: (
@@ -323,6 +352,7 @@ impl Scenario {
             duration_secs: Duration::from_secs(60),
             player_spawn: Vec3d::new(0.0, 0.0, -2.0),
             room: create_room(RoomType::Cube, 7.0),
+            room_style: RoomStyle::grid(1.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Sphere,
                 spawn: (
@@ -359,6 +389,7 @@ impl Scenario {
             duration_secs: Duration::from_secs(60),
             player_spawn: Vec3d::new(0.0, 0.0, -2.0),
             room: create_room(RoomType::Cube, 7.0),
+            room_style: RoomStyle::grid(1.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Sphere,
                 spawn: (
@@ -395,6 +426,7 @@ impl Scenario {
             duration_secs: Duration::from_secs(60),
             player_spawn: Vec3d::new(0.0, -4.0, 0.0),
             room: create_room(RoomType::Octagon, 5.0),
+            room_style: RoomStyle::checker_floor(1.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Sphere,
                 spawn: (
@@ -431,6 +463,7 @@ impl Scenario {
             duration_secs: Duration::from_secs(60),
             player_spawn: Vec3d::new(0.0, -1.5, 0.0),
             room: create_room(RoomType::Octagon, 4.0),
+            room_style: RoomStyle::checker_floor(1.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Bean,
                 spawn: (
@@ -467,6 +500,7 @@ impl Scenario {
             duration_secs: Duration::from_secs(60),
             player_spawn: Vec3d::new(0.0, -3.0, 0.0),
             room: create_room(RoomType::Cube, 10.0),
+            room_style: RoomStyle::grid(1.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Bean,
                 spawn: (
@@ -503,6 +537,7 @@ impl Scenario {
             duration_secs: Duration::from_secs(60),
             player_spawn: Vec3d::new(0.0, 0.0, -3.0),
             room: create_room(RoomType::Cube, 5.0),
+            room_style: RoomStyle::grid(1.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Sphere,
                 spawn: (
@@ -539,6 +574,7 @@ impl Scenario {
             duration_secs: Duration::from_secs(30),
             player_spawn: Vec3d::new(0.0, 1.0, -4.0),
             room: create_room(RoomType::Cube, 5.0),
+            room_style: RoomStyle::grid(1.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Block,
                 spawn: (
@@ -561,6 +597,7 @@ impl Scenario {
             duration_secs: Duration::from_secs(30),
             player_spawn: Vec3d::new(0.0, 1.0, -4.0),
             room: create_room(RoomType::Cube, 5.0),
+            room_style: RoomStyle::grid(1.0),
             t_settings: TargetSettings {
                 shape: TargetShape::Block,
                 spawn: (
@@ -579,7 +616,7 @@ impl Scenario {
     
 }
 
-fn load_all_scenarios() -> Vec<Scenario> {
+pub fn load_all_scenarios() -> Vec<Scenario> {
     let mut all_scenarios: Vec<Scenario> = Vec::new();
     all_scenarios.append(&mut static_clicking());
     all_scenarios.append(&mut dyn_clicking());
@@ -590,6 +627,15 @@ fn load_all_scenarios() -> Vec<Scenario> {
     all_scenarios
 }
 
+pub fn find_scenario(name: &str) -> Result<Scenario, EngineError> {
+    // Accepts the display name in any case, or its file name form ("floating_heads_small")
+    let slug = |n: &str| n.to_lowercase().replace(' ', "_");
+    load_all_scenarios()
+        .into_iter()
+        .find(|s| s.name.eq_ignore_ascii_case(name) || slug(&s.name) == slug(name))
+        .ok_or_else(|| EngineError::UnknownScenarioErr(name.to_string()))
+}
+
 fn static_clicking() -> Vec<Scenario> {
     vec![
         Scenario::jumbo_tf(),
@@ -665,7 +711,7 @@ pub fn get_scenarios(i: usize, colors: &Colors) -> Vec<Scenario> {
             println!("\n{}STATIC CLICKING{}", colors.blue, colors.reset);
             evasive_switching()
         },
-        0 | _ => {
+        _ => {
             println!("\n{}ALL SCENARIOS{}", colors.blue, colors.reset);
             load_all_scenarios()},
     }
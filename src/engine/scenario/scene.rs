@@ -1,4 +1,4 @@
-use crate::engine::{Mesh, Triangle};
+use crate::engine::{Mesh, Triangle, Vec3d};
 
 #[derive(Clone, Copy, Debug)]
 pub enum RoomType {
@@ -8,6 +8,63 @@ pub enum RoomType {
     Octagon
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Surface {
+    Floor,
+    Walls,
+    Ceiling,
+}
+impl Surface {
+    pub const ALL: [Surface; 3] = [Surface::Floor, Surface::Walls, Surface::Ceiling];
+
+    pub fn facing(normal: Vec3d) -> Self {
+        // Rooms are seen from the inside, so the floor's normal points up
+        match normal.y {
+            y if y > 0.7 => Surface::Floor,
+            y if y < -0.7 => Surface::Ceiling,
+            _ => Surface::Walls,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum SurfaceStyle {
+/*
+    How a room surface is drawn. size is the length in world units the pattern repeats after
+    (the width of one grid cell or checker square).
+*/
+    Plain, // environment.scene_color from config.toml
+    Grid { size: f32 },
+    Checker { size: f32 },
+    Png { file: &'static str, size: f32 }, // Looked up in assets/textures
+}
+
+#[derive(Clone, Debug)]
+pub struct RoomStyle {
+    pub floor: SurfaceStyle,
+    pub walls: SurfaceStyle,
+    pub ceiling: SurfaceStyle,
+}
+impl RoomStyle {
+    pub fn plain() -> Self {
+        Self { floor: SurfaceStyle::Plain, walls: SurfaceStyle::Plain, ceiling: SurfaceStyle::Plain }
+    }
+    pub fn grid(size: f32) -> Self {
+        Self { floor: SurfaceStyle::Grid { size }, walls: SurfaceStyle::Grid { size }, ceiling: SurfaceStyle::Grid { size } }
+    }
+    pub fn checker_floor(size: f32) -> Self {
+        // A checkered floor under plain grid walls, makes strafing speed easy to read
+        Self { floor: SurfaceStyle::Checker { size }, walls: SurfaceStyle::Grid { size }, ceiling: SurfaceStyle::Plain }
+    }
+    pub fn surface(&self, surface: Surface) -> &SurfaceStyle {
+        match surface {
+            Surface::Floor => &self.floor,
+            Surface::Walls => &self.walls,
+            Surface::Ceiling => &self.ceiling,
+        }
+    }
+}
+
 pub fn create_room(room_type:RoomType, radius: f32) -> Mesh {
 /*
     Center of a room will always be the origin (0,0,0).
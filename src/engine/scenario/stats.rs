@@ -1,31 +1,86 @@
-use std::time::SystemTime;
+use crate::engine::camera::Camera;
+use crate::engine::core::{Vec3d, BLUE, GREEN, RESET};
+use serde::{Deserialize, Serialize};
 
-use crate::engine::core::{BLUE, RESET};
+use crate::engine::scenario::{History, MousePath, Timeline, TrackingLog};
+
+// How many of the most recent runs are averaged for comparison
+pub const RECENT_RUNS: usize = 10;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoreKind {
+    // Scores of different kinds are on different scales and never compared with each other
+    #[default]
+    HitsAccuracy, // Hits * accuracy in %, every run recorded before score kinds existed
+    TimeOnTarget, // Time on target in % * 100, tracking runs
+}
 
 pub struct Statistic {
+    pub seed:u64,
     pub hits:u32,
     pub shots:u32,
     pub accuracy:f32,
-    pub scenario_starttime:SystemTime,
-    pub scenario_endtime:SystemTime,
-    pub scenario_ended:bool
+    pub kills:u32,
+    pub offsets:Vec<ShotOffset>,
+    pub timeline:Timeline,
+    pub mouse_path:MousePath,
+    pub tracking:Option<TrackingLog>, // Only logged for tracking scenarios
+    pub elapsed:f32, // Simulated run time in seconds, only advanced while playing (pauses do not count)
+}
+impl Default for Statistic {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl Statistic {
     pub fn new() -> Self {
+        Self::with_seed(rand::random())
+    }
+    pub fn with_seed(seed:u64) -> Self {
         Self {
+            seed,
             hits: 0,
             shots:0,
             accuracy:0.0,
-            scenario_starttime:SystemTime::now(),
-            scenario_endtime:SystemTime::now(),
-            scenario_ended: false
+            kills: 0,
+            offsets: Vec::new(),
+            timeline: Timeline::default(),
+            mouse_path: MousePath::default(),
+            tracking: None,
+            elapsed: 0.0,
         }
     }
+    fn current_second(&self) -> usize {
+        self.elapsed as usize
+    }
+    pub fn advance(&mut self, delta_time:f32) {
+        self.elapsed += delta_time;
+    }
     pub fn add_hit(&mut self) {
         self.hits += 1; self.shots += 1;
+        let sample = self.timeline.at(self.current_second());
+        sample.hits += 1; sample.shots += 1;
     }
     pub fn add_shot(&mut self) {
         self.shots += 1;
+        self.timeline.at(self.current_second()).shots += 1;
+    }
+    pub fn add_kill(&mut self) {
+        self.kills += 1;
+        self.timeline.at(self.current_second()).kills += 1;
+    }
+    pub fn record_tick(&mut self, delta_time:f32, on_target:bool) {
+        if on_target {
+            self.timeline.at(self.current_second()).time_on_target += delta_time;
+        }
+    }
+    pub fn record_render_frame(&mut self) {
+        // Rendering is decoupled from the simulation ticks, so frames are counted by the runtime
+        self.timeline.at(self.current_second()).frames += 1;
+    }
+    pub fn add_offset(&mut self, offset: ShotOffset) {
+        self.offsets.push(offset);
     }
     pub fn get_accuracy_p(&mut self) -> f32{
         // Accuracy in % only if shots > 0
@@ -34,21 +89,129 @@ impl Statistic {
         }
         self.accuracy
     }
-    pub fn end_scenario(&mut self) {
-        self.scenario_endtime = SystemTime::now();
-        self.scenario_ended = true;
+    pub fn score_kind(&self) -> ScoreKind {
+        if self.tracking.as_ref().and_then(|t| t.metrics()).is_some() {
+            ScoreKind::TimeOnTarget
+        } else {
+            ScoreKind::HitsAccuracy
+        }
+    }
+    pub fn score(&mut self) -> f32 {
+        // Tracking runs are scored by time on target (0 - 10000), the shot counter only reflects the fire rate there
+        if let Some(tracking) = self.tracking.as_ref().and_then(|t| t.metrics()) {
+            return tracking.on_target_pct * 100.0;
+        }
+        // Hits weighted by accuracy, so spraying does not pay off
+        self.hits as f32 * self.get_accuracy_p()
     }
     pub fn scenario_playtime(&self) -> u32 {
-        self.scenario_endtime.duration_since(self.scenario_starttime).unwrap().as_secs() as u32
+        self.elapsed as u32
     }
-    pub fn print_stats(&mut self, scenario_name:&String, avg_fps:u32) {
+    pub fn print_stats(&mut self, scenario_name:&str, avg_fps:u32, history:&History) {
         println!("\n{}--- RESULTS --- {}", BLUE, RESET);
         println!("\n{}{}{}", BLUE, scenario_name.to_uppercase(), RESET);
         println!("Duration: {} seconds", self.scenario_playtime());
-        println!("Hits: {}", self.hits);
-        println!("Shots: {}", self.shots);
-        println!("Accuracy: {:.2}%", self.get_accuracy_p());
+        let tracking = self.tracking.as_ref().and_then(|t| t.metrics());
+        match tracking {
+            // The shot counter depends on the fire rate, tracking is described by the per-frame error instead
+            Some(tracking) => tracking.print(),
+            None => {
+                println!("Hits: {}", self.hits);
+                println!("Shots: {}", self.shots);
+                println!("Kills: {}", self.kills);
+                println!("Accuracy: {:.2}%", self.get_accuracy_p());
+            }
+        }
+        let score = self.score();
+        println!("Score: {:.0}", score);
+        self.print_comparison(scenario_name, self.score_kind(), score, history);
+        if tracking.is_none() {
+            // Shot offsets and flicks only describe scenarios that are played shot by shot
+            if let Some((h, v)) = self.mean_angular_offset() {
+                println!("Average Aim Offset: {:.2}° horizontal, {:.2}° vertical", h, v);
+            }
+            if let Some(flicks) = self.mouse_path.metrics(self.kills) {
+                flicks.print();
+            }
+        }
         println!("Average FPS: {avg_fps}");
+        self.timeline.print_charts();
         println!("\n{}--- END RESULTS --- {}\n", BLUE, RESET);
     }
-}
\ No newline at end of file
+    fn print_comparison(&self, scenario_name:&str, kind:ScoreKind, score:f32, history:&History) {
+        // history only holds previous runs here, the current one is appended after printing
+        // Runs scored another way (e.g. tracking runs before they were scored by time on target) are left out
+        match history.personal_best(scenario_name, kind) {
+            Some(pb) if score > pb.score => {
+                println!("{}NEW PERSONAL BEST!{} (previous: {:.0}, +{:.0})", GREEN, RESET, pb.score, score - pb.score);
+            }
+            Some(pb) => println!("Personal Best: {:.0} ({:+.0})", pb.score, score - pb.score),
+            None => {
+                println!("{}First run of this scenario!{}", GREEN, RESET);
+                return;
+            }
+        }
+        if let Some(avg) = history.average_last(scenario_name, kind, RECENT_RUNS) {
+            println!("Average of last {} runs: {:.0} ({:+.0})", history.scored_runs(scenario_name, kind).count().min(RECENT_RUNS), avg, score - avg);
+        }
+        if let Some(rank) = history.percentile_rank(scenario_name, kind, score) {
+            println!("Better than or equal to {:.0}% of previous runs", rank);
+        }
+    }
+    pub fn mean_angular_offset(&self) -> Option<(f32, f32)> {
+        // Systematic over-/under-aim shows up as a mean that drifts away from (0, 0)
+        if self.offsets.is_empty() {
+            return None;
+        }
+        let n = self.offsets.len() as f32;
+        let (h, v) = self.offsets.iter().fold((0.0, 0.0), |(h, v), o| (h + o.angular.0, v + o.angular.1));
+        Some((h / n, v / n))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ShotOffset {
+/*
+    Where a single shot landed relative to the centre of the (nearest) target.
+    local is measured in target-local coordinates (world units from the target's centre),
+    angular is (horizontal, vertical) in degrees, positive meaning right of / above the centre.
+*/
+    pub hit: bool,
+    pub local: Vec3d,
+    pub angular: (f32, f32),
+}
+impl ShotOffset {
+    pub fn hit(camera: &Camera, hit_point: Vec3d, target_pos: Vec3d) -> Self {
+        Self {
+            hit: true,
+            local: hit_point - target_pos,
+            angular: angular_offset(camera, target_pos),
+        }
+    }
+    pub fn miss(camera: &Camera, target_pos: Vec3d) -> Self {
+        // Closest point of the aim ray to the target centre, so misses are comparable to hits
+        let dist_along_ray = camera.position.vec_to(&target_pos).dot(camera.look_dir).max(0.0);
+        let closest = camera.position + camera.look_dir * dist_along_ray;
+        Self {
+            hit: false,
+            local: closest - target_pos,
+            angular: angular_offset(camera, target_pos),
+        }
+    }
+}
+
+pub fn angular_offset(camera: &Camera, point: Vec3d) -> (f32, f32) {
+    // Difference between the camera's yaw/pitch and the yaw/pitch pointing exactly at the point
+    let to_point = camera.position.vec_to(&point).normalize();
+    let look = camera.look_dir;
+
+    let mut yaw_diff = look.x.atan2(look.z) - to_point.x.atan2(to_point.z);
+    if yaw_diff > std::f32::consts::PI {
+        yaw_diff -= 2.0 * std::f32::consts::PI;
+    } else if yaw_diff < -std::f32::consts::PI {
+        yaw_diff += 2.0 * std::f32::consts::PI;
+    }
+    let pitch_diff = look.y.clamp(-1.0, 1.0).asin() - to_point.y.clamp(-1.0, 1.0).asin();
+
+    (yaw_diff.to_degrees(), pitch_diff.to_degrees())
+}
@@ -1,10 +1,16 @@
 
-use crate::engine::{CONFIG, Mat4x4, Triangle, Vec3d, dyn_clamp_pos};
-use rand::{Rng, rngs::ThreadRng};
+use crate::engine::{CONFIG, Mat4x4, Triangle, Vec3d, camera::Camera, dyn_clamp_pos};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::collections::HashMap;
 
 pub struct Target {
-    // Triangles for target visuals
+    // Unique within a run, used to follow a target across recorded frames
+    pub id: u32,
+
+    // Triangles for target visuals and hit detection (round targets are drawn from TargetMeshes instead)
     pub tris: Vec<Triangle>,
+    pub shape: TargetShape,
+    pub bounds: f32, // Radius of a sphere around the position that contains the whole target
 
     // Target properties
     pub radius: f32, // stored to avoid clipping
@@ -13,6 +19,7 @@ pub struct Target {
     // Variables concerning target movement
     pub movement: Option<MovingTarget>,
     pub position: Vec3d,
+    pub prev_position: Vec3d, // Position before the last simulation tick, rendering interpolates between both
     pub velocity: Vec3d,
 }
 #[derive(Debug, Clone, Copy)]
@@ -24,7 +31,7 @@ pub struct MovingTarget {
     pub vel_bounds: (Vec3d, Vec3d), //  How slow/fast does it move on each axis
     pub interval_dir_change: f32, //  How long since last direction change
 }
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum TargetShape {
     Block,
     Sphere,
@@ -34,7 +41,9 @@ pub enum TargetShape {
 pub struct TargetVec {
     pub vec: Vec<Target>,
     pub settings: TargetSettings,
-    pub old: Option<Vec3d>
+    pub old: Option<Vec3d>,
+    pub rng: StdRng, // Seeded per run, drives both spawning and movement
+    pub next_id: u32,
 }
 #[derive(Clone, Copy)]
 pub struct TargetSettings {
@@ -46,6 +55,17 @@ pub struct TargetSettings {
     pub movement: Option<MovingTarget>,
 }
 impl Target {
+    pub fn new(t_settings: TargetSettings, pos:Vec3d, velo:Vec3d) -> Self {
+        match t_settings.shape {
+            TargetShape::Block => Target::block(t_settings, pos, velo),
+
+            TargetShape::Square => Target::square(t_settings, pos, velo),
+
+            TargetShape::Sphere => Target::sphere(t_settings, pos, velo),
+
+            TargetShape::Bean => Target::bean(t_settings, pos, velo),
+        }
+    }
     pub fn block(t_settings: TargetSettings, pos:Vec3d, velo:Vec3d) -> Self {
         let rad = t_settings.rad;
         Self {
@@ -63,10 +83,14 @@ impl Target {
                 Triangle::new((rad, -rad, rad), (-rad, -rad, rad), (-rad, -rad, -rad)),
                 Triangle::new((rad, -rad, rad), (-rad, -rad, -rad), (rad, -rad, -rad)),
             ],
+            id: 0,
+            shape: t_settings.shape,
+            bounds: rad * 3f32.sqrt(), // Distance to a corner
             movement: t_settings.movement,
             hp: t_settings.hp,
             radius:rad,
             position: pos,
+            prev_position: pos,
             velocity: velo,
         }
     }
@@ -104,100 +128,62 @@ impl Target {
         let v3r = mat_rot * v3;
 
         Target {
+            id: 0,
             tris: vec![
-                Triangle { p: [v0r, v1r, v2r] },
-                Triangle { p: [v0r, v2r, v3r] },
+                Triangle::flat([v0r, v1r, v2r]),
+                Triangle::flat([v0r, v2r, v3r]),
             ],
+            shape: t_settings.shape,
+            bounds: rad * 3f32.sqrt(), // Distance to a corner, the rotation keeps it
             movement: t_settings.movement,
             radius:rad,
             hp: t_settings.hp,
             position: pos,
+            prev_position: pos,
             velocity: velo,
         }
     }
     pub fn sphere(t_settings:TargetSettings, position:Vec3d, velo:Vec3d) -> Self {
-        let detail = CONFIG.targets.sphere_detail;
         let rad = t_settings.rad;
-
-        let mut tris = Vec::with_capacity(detail * detail * 2);
-        for i in 0..detail {
-            let phi1 = std::f32::consts::PI * (i as f32 / detail as f32 - 0.5);
-            let phi2 = std::f32::consts::PI * ((i + 1) as f32 / detail as f32 - 0.5);
-            let y1 = rad * phi1.sin();
-            let y2 = rad * phi2.sin();
-            let r1 = rad * phi1.cos();
-            let r2 = rad * phi2.cos();
-
-            for j in 0..detail {
-                let theta1 = 2.0 * std::f32::consts::PI * (j as f32 / detail as f32);
-                let theta2 = 2.0 * std::f32::consts::PI * ((j + 1) as f32 / detail as f32);
-
-                let p1 = (r1 * theta1.cos(), y1, r1 * theta1.sin());
-                let p2 = (r2 * theta1.cos(), y2, r2 * theta1.sin());
-                let p3 = (r2 * theta2.cos(), y2, r2 * theta2.sin());
-                let p4 = (r1 * theta2.cos(), y1, r1 * theta2.sin());
-
-                tris.push(Triangle::new(p1, p2, p3));
-                tris.push(Triangle::new(p1, p3, p4));
-            }
-        }
+        let tris = sphere_tris(rad, CONFIG.targets.sphere_detail);
 
         Self {
+            id: 0,
+            bounds: bounding_radius(&tris),
             tris,
+            shape: t_settings.shape,
             movement: t_settings.movement,
             radius: rad,
             hp: t_settings.hp,
             position,
+            prev_position: position,
             velocity: velo,
         }
     }
     pub fn bean(t_settings: TargetSettings, pos:Vec3d, velo:Vec3d) -> Self {
-        let mut tris = Vec::new();
-
-        let rad = t_settings.rad; let stretch_y = rad * 5.0;
-
-        let detail = CONFIG.targets.sphere_detail;
-
-        for i in 0..detail {
-            let phi1 = std::f32::consts::PI * (i as f32 / detail as f32 - 0.5);
-            let phi2 = std::f32::consts::PI * ((i + 1) as f32 / detail as f32 - 0.5);
-            let y1 = rad * phi1.sin();
-            let y2 = rad * phi2.sin();
-            let r1 = rad * phi1.cos();
-            let r2 = rad * phi2.cos();
-
-            fn non_uniform_scl(c_rad:f32, stretch_y:f32, r: f32, y:f32, theta:f32) -> (f32, f32, f32) {
-                (c_rad * r * theta.cos(),
-                 stretch_y * y,
-                 c_rad * r * theta.sin()
-                )
-            }
-
-            for j in 0..detail {
-                let theta1 = 2.0 * std::f32::consts::PI * (j as f32 / detail as f32);
-                let theta2 = 2.0 * std::f32::consts::PI * ((j + 1) as f32 / detail as f32);
-
-                let p1 = non_uniform_scl(rad, stretch_y, r1, y1, theta1);
-                let p2 = non_uniform_scl(rad, stretch_y, r2, y2, theta1);
-                let p3 = non_uniform_scl(rad, stretch_y, r2, y2, theta2);
-                let p4 = non_uniform_scl(rad, stretch_y, r1, y1, theta2);
-
-                tris.push(Triangle::new(p1, p2, p3));
-                tris.push(Triangle::new(p1, p3, p4));
-            }
-        }
+        let tris = bean_tris(t_settings.rad, CONFIG.targets.sphere_detail);
 
         Self {
+            id: 0,
+            bounds: bounding_radius(&tris),
             tris,
+            shape: t_settings.shape,
             radius:t_settings.rad,
             hp: t_settings.hp,
             movement: t_settings.movement,
             position:pos,
+            prev_position: pos,
             velocity: velo,
         }
     }
 
-    pub fn random_movement(&mut self, cam_pos:Vec3d, rng: &mut ThreadRng, delta_time:f32) {
+    pub fn render_position(&self, alpha: f32) -> Vec3d {
+        // alpha is how far the renderer is between the previous and the current tick (0..1)
+        self.prev_position + (self.position - self.prev_position) * alpha
+    }
+
+    pub fn random_movement(&mut self, cam_pos:Vec3d, rng: &mut StdRng, delta_time:f32) {
+        self.prev_position = self.position;
 
         if let Some(ref mut m) = self.movement {
 
@@ -259,21 +245,164 @@ impl Target {
         }
     }
 }
+fn sphere_tris(rad: f32, detail: usize) -> Vec<Triangle> {
+    let mut tris = Vec::with_capacity(detail * detail * 2);
+    for i in 0..detail {
+        let phi1 = std::f32::consts::PI * (i as f32 / detail as f32 - 0.5);
+        let phi2 = std::f32::consts::PI * ((i + 1) as f32 / detail as f32 - 0.5);
+        let y1 = rad * phi1.sin();
+        let y2 = rad * phi2.sin();
+        let r1 = rad * phi1.cos();
+        let r2 = rad * phi2.cos();
+
+        for j in 0..detail {
+            let theta1 = 2.0 * std::f32::consts::PI * (j as f32 / detail as f32);
+            let theta2 = 2.0 * std::f32::consts::PI * ((j + 1) as f32 / detail as f32);
+
+            let p1 = Vec3d::new(r1 * theta1.cos(), y1, r1 * theta1.sin());
+            let p2 = Vec3d::new(r2 * theta1.cos(), y2, r2 * theta1.sin());
+            let p3 = Vec3d::new(r2 * theta2.cos(), y2, r2 * theta2.sin());
+            let p4 = Vec3d::new(r1 * theta2.cos(), y1, r1 * theta2.sin());
+
+            // On a sphere around the origin the normal is just the direction of the point
+            let n = |p: Vec3d| p.normalize();
+            tris.push(Triangle::with_normals([p1, p2, p3], [n(p1), n(p2), n(p3)]));
+            tris.push(Triangle::with_normals([p1, p3, p4], [n(p1), n(p3), n(p4)]));
+        }
+    }
+    tris
+}
+
+fn bean_tris(rad: f32, detail: usize) -> Vec<Triangle> {
+    let mut tris = Vec::with_capacity(detail * detail * 2);
+
+    let stretch_y = rad * 5.0;
+
+/*
+    The bean is an ellipsoid with semi-axes (a, b, a), a = rad * rad and b = stretch_y * rad.
+    Its surface normal at p is the gradient of x²/a² + y²/b² + z²/a², i.e. (x/a², y/b², z/a²).
+*/  let (a2, b2) = ((rad * rad).powi(2), (stretch_y * rad).powi(2));
+    let n = |p: Vec3d| Vec3d::new(p.x / a2, p.y / b2, p.z / a2).normalize();
+
+    for i in 0..detail {
+        let phi1 = std::f32::consts::PI * (i as f32 / detail as f32 - 0.5);
+        let phi2 = std::f32::consts::PI * ((i + 1) as f32 / detail as f32 - 0.5);
+        let y1 = rad * phi1.sin();
+        let y2 = rad * phi2.sin();
+        let r1 = rad * phi1.cos();
+        let r2 = rad * phi2.cos();
+
+        fn non_uniform_scl(c_rad:f32, stretch_y:f32, r: f32, y:f32, theta:f32) -> Vec3d {
+            Vec3d::new(c_rad * r * theta.cos(),
+             stretch_y * y,
+             c_rad * r * theta.sin()
+            )
+        }
+
+        for j in 0..detail {
+            let theta1 = 2.0 * std::f32::consts::PI * (j as f32 / detail as f32);
+            let theta2 = 2.0 * std::f32::consts::PI * ((j + 1) as f32 / detail as f32);
+
+            let p1 = non_uniform_scl(rad, stretch_y, r1, y1, theta1);
+            let p2 = non_uniform_scl(rad, stretch_y, r2, y2, theta1);
+            let p3 = non_uniform_scl(rad, stretch_y, r2, y2, theta2);
+            let p4 = non_uniform_scl(rad, stretch_y, r1, y1, theta2);
+
+            tris.push(Triangle::with_normals([p1, p2, p3], [n(p1), n(p2), n(p3)]));
+            tris.push(Triangle::with_normals([p1, p3, p4], [n(p1), n(p3), n(p4)]));
+        }
+    }
+    tris
+}
+
+fn bounding_radius(tris: &[Triangle]) -> f32 {
+    // Shapes are built around the origin, so the farthest vertex gives the radius of a sphere around the whole target
+    tris.iter().flat_map(|tri| tri.p).map(|p| p.length()).fold(0.0, f32::max)
+}
+
+// Tessellations render meshes can pick from, and the longest a sphere segment may appear on screen
+const LOD_LEVELS: [usize; 6] = [6, 8, 12, 16, 24, 32];
+const MAX_SEGMENT_PX: f32 = 6.0;
+
+#[derive(Default)]
+pub struct TargetMeshes {
+/*
+    Render meshes of round targets at different levels of detail, generated on first use.
+    All targets of a scenario share their shape and size, so each level is built once per run.
+    Hit detection keeps using Target::tris at the configured sphere_detail, so scores and replays
+    do not depend on how large a target appears on screen. Every level is shrunk to fit inside that
+    hit mesh (see hit_mesh_inradius), so whatever is drawn of a target can also be hit.
+*/
+    meshes: HashMap<(TargetShape, u32, usize), Vec<Triangle>>, // By shape, radius bits and detail
+    inset: Option<f32>, // hit_mesh_inradius at the configured sphere_detail, computed once
+}
+impl TargetMeshes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn for_screen_radius<'a>(&'a mut self, target: &'a Target, radius_px: f32) -> &'a [Triangle] {
+    /*
+        Picks the lowest detail whose segments stay below MAX_SEGMENT_PX around the outline.
+        Blocks and squares are returned as they are.
+    */
+        let build: fn(f32, usize) -> Vec<Triangle> = match target.shape {
+            TargetShape::Sphere => sphere_tris,
+            TargetShape::Bean => bean_tris,
+            TargetShape::Block | TargetShape::Square => return &target.tris,
+        };
+        let circumference = 2.0 * std::f32::consts::PI * radius_px;
+        let detail = LOD_LEVELS.into_iter()
+            .find(|&d| circumference / d as f32 <= MAX_SEGMENT_PX)
+            .unwrap_or(LOD_LEVELS[LOD_LEVELS.len() - 1]);
+        let inset = *self.inset.get_or_insert_with(|| hit_mesh_inradius(CONFIG.targets.sphere_detail));
+        self.meshes
+            .entry((target.shape, target.radius.to_bits(), detail))
+            .or_insert_with(|| {
+                // Only the positions move inwards, the normals still describe the round surface
+                build(target.radius, detail).into_iter()
+                    .map(|tri| Triangle::with_normals(tri.p.map(|p| p * inset), tri.n))
+                    .collect()
+            })
+    }
+}
+
+fn hit_mesh_inradius(detail: usize) -> f32 {
+/*
+    Radius of the largest sphere around the origin that fits inside a unit sphere mesh of this detail,
+    i.e. the distance to its closest face. Render meshes scaled by it lie inside the hit mesh at any detail:
+    their vertices are on the round surface, which shrunk by this factor is inside every face of the hit mesh.
+    Beans are sphere meshes stretched per axis, stretching keeps what is inside what, so the same factor fits them.
+    The triangles at the poles have two equal corners and no plane of their own, they are skipped.
+*/
+    sphere_tris(1.0, detail.max(1)).iter()
+        .filter_map(|tri| {
+            let normal = tri.p[0].vec_to(&tri.p[1]).cross(tri.p[0].vec_to(&tri.p[2]));
+            if normal.length() < 1e-6 {
+                return None;
+            }
+            Some(normal.normalize().dot(tri.p[0]).abs())
+        })
+        .fold(1.0, f32::min)
+}
+
 impl TargetVec {
-    pub fn empty(settings: &TargetSettings) -> Self {
+    pub fn empty(settings: &TargetSettings, seed: u64) -> Self {
         Self {
             vec: Vec::with_capacity(settings.count),
-            settings: settings.clone(),
+            settings: *settings,
             old:None,
+            rng: StdRng::seed_from_u64(seed),
+            next_id: 0,
         }
     }
-    pub fn init(settings: &TargetSettings) -> Self {
-        let mut empty = TargetVec::empty(settings);
+    pub fn init(settings: &TargetSettings, seed: u64) -> Self {
+        let mut empty = TargetVec::empty(settings, seed);
         empty.fill();
         empty
     }
     pub fn add_target(&mut self) {
-        let mut rng = rand::thread_rng();
+        let rng = &mut self.rng;
         let start_vel = if let Some(ref mut m) = self.settings.movement {
             let min = m.vel_bounds.0;
             Vec3d::new(
@@ -291,7 +420,7 @@ impl TargetVec {
             let rad_2x = self.settings.rad * 2.0;
             'outer: loop {
                 //create a random spawn location
-                let spawn = Vec3d::from_rng_range(spawn_a, spawn_b, &mut rng);
+                let spawn = Vec3d::from_rng_range(spawn_a, spawn_b, &mut self.rng);
 
                 // Check if spawn loc collides with existing target
                 if n_loops < 50 { // prevent infinite looping
@@ -316,25 +445,28 @@ impl TargetVec {
             spawn_a
         };
 
-        match self.settings.shape {
-            TargetShape::Block => self.vec.push(Target::block(self.settings, spawn, start_vel)),
-
-            TargetShape::Square => self.vec.push(Target::square(self.settings, spawn, start_vel)),
-
-            TargetShape::Sphere => self.vec.push(Target::sphere(self.settings, spawn, start_vel)),
-
-            TargetShape::Bean => self.vec.push(Target::bean(self.settings, spawn, start_vel)),
-        }
+        let mut target = Target::new(self.settings, spawn, start_vel);
+        target.id = self.next_id;
+        self.next_id += 1;
+        self.vec.push(target);
     }
     pub fn fill(&mut self)  {
         for _ in 0..self.settings.count {
             self.add_target();
         }
     }
+    pub fn nearest_to_aim(&self, camera: &Camera) -> Option<&Target> {
+        // Target with the smallest angle between the crosshair ray and its centre
+        self.vec.iter().max_by(|a, b| {
+            let cos_a = camera.position.vec_to(&a.position).normalize().dot(camera.look_dir);
+            let cos_b = camera.position.vec_to(&b.position).normalize().dot(camera.look_dir);
+            cos_a.partial_cmp(&cos_b).unwrap_or(std::cmp::Ordering::Equal)
+        })
+    }
 }
 
 
-pub fn new_direction(movement_target: &mut MovingTarget, velo:f32, rng:&mut ThreadRng, min:f32, max:f32) -> f32 {
+pub fn new_direction(movement_target: &mut MovingTarget, velo:f32, rng:&mut StdRng, min:f32, max:f32) -> f32 {
     if velo > 0.0 {
         if rng.gen_bool(movement_target.p_change_dir / 100.0) {
             -min
@@ -351,3 +483,35 @@ pub fn new_direction(movement_target: &mut MovingTarget, velo:f32, rng:&mut Thre
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_meshes_fit_inside_the_hit_mesh() {
+        let rad = 0.7;
+        let hit_detail = 8;
+        let inset = hit_mesh_inradius(hit_detail);
+        let shapes: [fn(f32, usize) -> Vec<Triangle>; 2] = [sphere_tris, bean_tris];
+        for build in shapes {
+            let hit_mesh = build(rad, hit_detail);
+            for detail in LOD_LEVELS {
+                for p in build(rad, detail).iter().flat_map(|tri| tri.p) {
+                    let p = p * inset;
+                    for face in &hit_mesh {
+                        let normal = face.p[0].vec_to(&face.p[1]).cross(face.p[0].vec_to(&face.p[2]));
+                        if normal.length() < 1e-6 {
+                            continue;
+                        }
+                        // Faces of a convex mesh around the origin have it on their inner side
+                        let normal = normal.normalize();
+                        let plane = normal.dot(face.p[0]);
+                        let (normal, plane) = if plane < 0.0 { (normal * -1.0, -plane) } else { (normal, plane) };
+                        assert!(normal.dot(p) <= plane + 1e-4, "vertex {:?} at detail {} is outside the hit mesh", p, detail);
+                    }
+                }
+            }
+        }
+    }
+}
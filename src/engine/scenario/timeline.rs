@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+const CHART_HEIGHT: usize = 6;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SecondSample {
+    pub hits: u32,
+    pub shots: u32,
+    pub kills: u32,
+    pub time_on_target: f32, // Seconds the crosshair rested on a target during this second
+    pub frames: u32, // Rendered frames, equals the FPS of this second
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Timeline {
+    pub seconds: Vec<SecondSample>,
+}
+impl Timeline {
+    pub fn at(&mut self, second: usize) -> &mut SecondSample {
+        if self.seconds.len() <= second {
+            self.seconds.resize(second + 1, SecondSample::default());
+        }
+        &mut self.seconds[second]
+    }
+
+    pub fn print_charts(&self) {
+        if self.seconds.is_empty() {
+            return;
+        }
+        let hits: Vec<f32> = self.seconds.iter().map(|s| s.hits as f32).collect();
+        let on_target: Vec<f32> = self.seconds.iter().map(|s| s.time_on_target * 100.0).collect();
+        let fps: Vec<f32> = self.seconds.iter().map(|s| s.frames as f32).collect();
+
+        print_chart("Hits per second", &hits);
+        print_chart("Time on target (%)", &on_target);
+        print_chart("FPS", &fps);
+
+        // Fading towards the end of a run shows up when comparing the thirds
+        let thirds = |values: &[f32]| -> [f32; 3] {
+            let len = values.len();
+            let mut out = [0.0; 3];
+            for (i, part) in out.iter_mut().enumerate() {
+                let slice = &values[len * i / 3..len * (i + 1) / 3];
+                if !slice.is_empty() {
+                    *part = slice.iter().sum::<f32>() / slice.len() as f32;
+                }
+            }
+            out
+        };
+        let h = thirds(&hits);
+        let t = thirds(&on_target);
+        println!("Hits/s by third: {:.2} | {:.2} | {:.2}", h[0], h[1], h[2]);
+        println!("On target by third: {:.0}% | {:.0}% | {:.0}%", t[0], t[1], t[2]);
+    }
+}
+
+fn print_chart(title: &str, values: &[f32]) {
+/*
+    Vertical bar chart with one column per second of the run:
+
+     12 |   #  #
+        | ###### #
+      0 +---------
+         0s      10s
+*/
+    let max = values.iter().cloned().fold(0.0, f32::max);
+    let label_width = format!("{:.0}", max).len().max(1);
+
+    println!("\n{}", title);
+    for row in (1..=CHART_HEIGHT).rev() {
+        let threshold = max * (row as f32 - 0.5) / CHART_HEIGHT as f32;
+        let label = if row == CHART_HEIGHT { format!("{:.0}", max) } else { String::new() };
+        let bars: String = values.iter().map(|&v| if max > 0.0 && v >= threshold { '#' } else { ' ' }).collect();
+        println!("{:>w$} |{}", label, bars, w = label_width);
+    }
+    println!("{:>w$} +{}", 0, "-".repeat(values.len()), w = label_width);
+
+    // Mark every 10 seconds along the time axis
+    let mut axis = String::new();
+    let mut second = 0;
+    while second < values.len() {
+        let mark = format!("{}s", second);
+        axis.push_str(&format!("{:<10}", mark));
+        second += 10;
+    }
+    println!("{:>w$}  {}", "", axis.trim_end(), w = label_width);
+}
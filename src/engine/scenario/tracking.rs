@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+
+use crate::engine::camera::Camera;
+use crate::engine::core::Vec3d;
+use crate::engine::scenario::Target;
+
+const MAX_REACTION_SECS: f32 = 1.0; // Reactions slower than this are not counted, the player most likely switched focus
+const REACT_DEG_PER_SEC: f32 = 5.0; // The crosshair has to move at least this fast to count as following
+
+#[derive(Default)]
+pub struct TrackingLog {
+/*
+    Samples the angular error between the crosshair and the nearest target centre every frame.
+    Sums are weighted by frame time, so the results do not depend on the frame rate.
+*/
+    total_time: f32,
+    on_target_time: f32,
+    error_sum: f32,
+    error_sq_sum: f32,
+    prev: Option<TrackedFrame>,
+    pending_reaction: Option<f32>, // Seconds since the tracked target flipped direction
+    reaction_lags: Vec<f32>,
+}
+
+#[derive(Clone, Copy)]
+struct TrackedFrame {
+    target_id: u32,
+    velocity: Vec3d,
+    target_angles: (f32, f32), // Yaw/pitch from the camera to the target centre in degrees
+    camera_angles: (f32, f32),
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TrackingMetrics {
+    pub on_target_pct: f32,
+    pub mean_error_deg: f32,
+    pub rms_error_deg: f32,
+    pub reactions: u32,
+    pub reaction_lag_ms: f32, // Average over all counted direction changes
+}
+
+impl TrackingLog {
+    pub fn record_frame(&mut self, delta_time: f32, camera: &Camera, nearest: Option<&Target>, on_target: bool) {
+        self.total_time += delta_time;
+        if on_target {
+            self.on_target_time += delta_time;
+        }
+        let Some(target) = nearest else {
+            self.prev = None;
+            self.pending_reaction = None;
+            return;
+        };
+
+        let to_target = camera.position.vec_to(&target.position).normalize();
+        let error = to_target.dot(camera.look_dir).clamp(-1.0, 1.0).acos().to_degrees();
+        self.error_sum += error * delta_time;
+        self.error_sq_sum += error * error * delta_time;
+
+        let frame = TrackedFrame {
+            target_id: target.id,
+            velocity: target.velocity,
+            target_angles: (to_target.x.atan2(to_target.z).to_degrees(), to_target.y.clamp(-1.0, 1.0).asin().to_degrees()),
+            camera_angles: (camera.yaw.to_degrees(), camera.pitch.to_degrees()),
+        };
+        if let Some(prev) = self.prev.filter(|p| p.target_id == frame.target_id) {
+            self.update_reaction(delta_time, &prev, &frame);
+        } else {
+            self.pending_reaction = None;
+        }
+        self.prev = Some(frame);
+    }
+
+    fn update_reaction(&mut self, delta_time: f32, prev: &TrackedFrame, frame: &TrackedFrame) {
+    /*
+        A direction change starts when the target's velocity flips on any axis (including bounces off its bounds). The player has reacted once the
+        crosshair moves the same way the target now moves across the screen.
+    */
+        let (a, b) = (prev.velocity, frame.velocity);
+        if a.x * b.x < 0.0 || a.y * b.y < 0.0 || a.z * b.z < 0.0 {
+            self.pending_reaction = Some(0.0);
+            return;
+        }
+        let Some(since) = self.pending_reaction.as_mut() else { return };
+        *since += delta_time;
+
+        let target_rate = (wrap_deg(frame.target_angles.0 - prev.target_angles.0), frame.target_angles.1 - prev.target_angles.1);
+        let camera_rate = (wrap_deg(frame.camera_angles.0 - prev.camera_angles.0), frame.camera_angles.1 - prev.camera_angles.1);
+        let camera_speed = camera_rate.0.hypot(camera_rate.1) / delta_time.max(f32::EPSILON);
+
+        if target_rate.0 * camera_rate.0 + target_rate.1 * camera_rate.1 > 0.0 && camera_speed > REACT_DEG_PER_SEC {
+            self.reaction_lags.push(*since);
+            self.pending_reaction = None;
+        } else if *since > MAX_REACTION_SECS {
+            self.pending_reaction = None;
+        }
+    }
+
+    pub fn metrics(&self) -> Option<TrackingMetrics> {
+        if self.total_time <= 0.0 {
+            return None;
+        }
+        let reactions = self.reaction_lags.len();
+        Some(TrackingMetrics {
+            on_target_pct: self.on_target_time / self.total_time * 100.0,
+            mean_error_deg: self.error_sum / self.total_time,
+            rms_error_deg: (self.error_sq_sum / self.total_time).sqrt(),
+            reactions: reactions as u32,
+            reaction_lag_ms: if reactions > 0 { self.reaction_lags.iter().sum::<f32>() / reactions as f32 * 1000.0 } else { 0.0 },
+        })
+    }
+}
+
+impl TrackingMetrics {
+    pub fn print(&self) {
+        println!("Time on Target: {:.1}%", self.on_target_pct);
+        println!("Tracking Error: {:.2}° mean, {:.2}° RMS", self.mean_error_deg, self.rms_error_deg);
+        if self.reactions > 0 {
+            println!("Reaction to Direction Changes: {:.0} ms ({} changes)", self.reaction_lag_ms, self.reactions);
+        }
+    }
+}
+
+fn wrap_deg(angle: f32) -> f32 {
+    // Yaw differences across the 0/360 seam
+    let wrapped = angle.rem_euclid(360.0);
+    if wrapped > 180.0 { wrapped - 360.0 } else { wrapped }
+}
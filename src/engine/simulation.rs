@@ -0,0 +1,230 @@
+use winit::{event::MouseButton, keyboard::KeyCode};
+
+use crate::engine::{
+    CONFIG, Vec3d, camera::Camera, input::InputState, replay::{FrameState, Recording, ReplayEvent, StateTrack, FLAG_HIT, FLAG_SHOT}, rendering::target_aimed_at,
+    scenario::{Scenario, ShotOffset, Statistic, TargetVec, TrackingLog},
+};
+
+const MAX_CATCH_UP_SECS: f32 = 0.25;
+
+pub struct Simulation {
+/*
+    Everything that decides the outcome of a run: camera, input, targets and statistics.
+    It never touches the window, the renderer or audio, so the exact same code path
+    can be driven by live winit events or by the events of a replay file.
+*/
+    pub camera: Camera,
+    pub input: InputState,
+    pub stats: Statistic,
+    pub targets: TargetVec,
+    pub move_speed: f32,
+    pub time: f32, // Total simulated time, keeps running before the run timer starts (drives the gun)
+    pub tick_duration: f32,
+    accumulator: f32, // Real time that has not been simulated yet, always less than one tick
+    pub start_on_first_hit: bool,
+    pub recording: Option<Recording>,
+    pub states: Option<StateTrack>, // What each frame looked like, for the replay viewer
+}
+
+#[derive(Default)]
+pub struct FrameOutcome {
+    pub gun_shot: bool,
+    pub targets_hit: u32,
+    pub on_target: bool,
+}
+
+impl Simulation {
+    pub fn new(scenario: &mut Scenario, seed: u64, record: bool) -> Self {
+        let mut stats = Statistic::with_seed(seed);
+        if scenario.aiming_type.is_tracking() {
+            stats.tracking = Some(TrackingLog::default());
+        }
+        let recording = if record { Some(Recording::new(&scenario.name, seed)) } else { None };
+        let states = if record { Some(StateTrack::new(&scenario.name)) } else { None };
+        scenario.gun.reset();
+        Self {
+            camera: Camera::new(scenario.player_spawn),
+            input: InputState::new(),
+            targets: TargetVec::init(&scenario.t_settings, seed),
+            stats,
+            move_speed: CONFIG.input.move_speed,
+            time: 0.0,
+            tick_duration: 1.0 / CONFIG.run.tick_rate.max(1) as f32,
+            accumulator: 0.0,
+            start_on_first_hit: CONFIG.run.start_on_first_hit,
+            recording,
+            states,
+        }
+    }
+
+    pub fn from_recording(scenario: &mut Scenario, recording: &Recording) -> Self {
+        // Settings are taken from the replay, not the local config, so the run is reproduced exactly
+        let mut sim = Self::new(scenario, recording.seed, false);
+        sim.camera.sensitivity = recording.sensitivity;
+        sim.move_speed = recording.move_speed;
+        sim.start_on_first_hit = recording.start_on_first_hit;
+        sim
+    }
+
+    fn record(&mut self, event: ReplayEvent) {
+        if let Some(recording) = &mut self.recording {
+            recording.push(event);
+        }
+    }
+
+    pub fn mouse_motion(&mut self, dx: f64, dy: f64) {
+        self.record(ReplayEvent::MouseMotion { dx: dx as f32, dy: dy as f32 });
+        self.camera.update_yaw_pitch(dx, dy);
+    }
+    pub fn key_pressed(&mut self, key: KeyCode) {
+        self.record(ReplayEvent::KeyPressed(key));
+        self.input.pressed_key(key);
+    }
+    pub fn key_released(&mut self, key: KeyCode) {
+        self.record(ReplayEvent::KeyReleased(key));
+        self.input.released_key(key);
+    }
+    pub fn mouse_pressed(&mut self, button: MouseButton) {
+        self.record(ReplayEvent::MousePressed(button));
+        self.input.pressed_mouse(button);
+    }
+    pub fn mouse_released(&mut self, button: MouseButton) {
+        self.record(ReplayEvent::MouseReleased(button));
+        self.input.released_mouse(button);
+    }
+
+    pub fn apply(&mut self, scenario: &mut Scenario, event: ReplayEvent) -> Option<FrameOutcome> {
+        // Feeds a recorded event through the same path live input takes
+        match event {
+            ReplayEvent::Frame { delta_time } => return Some(self.frame(scenario, delta_time)),
+            ReplayEvent::MouseMotion { dx, dy } => self.mouse_motion(dx as f64, dy as f64),
+            ReplayEvent::KeyPressed(key) => self.key_pressed(key),
+            ReplayEvent::KeyReleased(key) => self.key_released(key),
+            ReplayEvent::MousePressed(btn) => self.mouse_pressed(btn),
+            ReplayEvent::MouseReleased(btn) => self.mouse_released(btn),
+        }
+        None
+    }
+
+    pub fn advance(&mut self, scenario: &mut Scenario, real_delta: f32) -> FrameOutcome {
+    /*
+        Runs as many fixed ticks as fit into the real time that passed, so movement, fire rate and scoring
+        behave the same at any frame rate. The leftover time is carried over to the next call.
+        Long stalls (e.g. dragging the window) are capped instead of being simulated all at once.
+    */
+        self.accumulator += real_delta.min(MAX_CATCH_UP_SECS);
+        let duration = scenario.duration_secs.as_secs_f32();
+        let mut outcome = FrameOutcome::default();
+        while self.accumulator >= self.tick_duration && self.stats.elapsed < duration {
+            self.accumulator -= self.tick_duration;
+            let tick = self.frame(scenario, self.tick_duration);
+            outcome.gun_shot |= tick.gun_shot;
+            outcome.targets_hit += tick.targets_hit;
+            outcome.on_target |= tick.on_target;
+        }
+        outcome
+    }
+
+    pub fn interpolation_alpha(&self) -> f32 {
+        (self.accumulator / self.tick_duration).clamp(0.0, 1.0)
+    }
+
+    pub fn frame(&mut self, scenario: &mut Scenario, delta_time: f32) -> FrameOutcome {
+        self.record(ReplayEvent::Frame { delta_time });
+        self.time += delta_time;
+        let now = self.time;
+
+        // With start_on_first_hit, setup before the first hit does not count: no time, shots or tracking until then
+        let run_started = !self.start_on_first_hit || self.stats.hits > 0;
+
+        self.camera.update_look_dir();
+        if scenario.allow_movement {
+            self.input.handle_movement(&mut self.camera, self.move_speed * delta_time);
+        }
+        self.camera.update_view_matrix();
+
+    /*
+        gun_shot should be set to true (depending on scenario config) upon mouse click or hold (only if gun can shoot).
+    */  let mut outcome = FrameOutcome::default();
+        if (!scenario.gun.automatic && self.input.mouse_buttons_just_pressed.contains(&MouseButton::Left)) ||
+        (scenario.gun.automatic && self.input.mouse_buttons_pressed.contains(&MouseButton::Left) && scenario.gun.can_shoot(now)) {
+            scenario.gun.shoot(now);
+            outcome.gun_shot = true;
+        }
+
+    /*
+        Target movement and hit detection, the projection for rendering happens afterwards in the runtime
+    */  let mut shot_offset: Option<ShotOffset> = None;
+        let mut aimed_target: Option<Vec3d> = None; // Target the shot was meant for, used by the flick analysis
+        let mut kills = 0;
+        for target in &mut self.targets.vec {
+            target.random_movement(self.camera.position, &mut self.targets.rng, delta_time);
+            let aimed_at = target_aimed_at(target, &self.camera);
+            if aimed_at.is_some() {
+                outcome.on_target = true;
+            }
+            if let Some(hit_point) = aimed_at.filter(|_| outcome.gun_shot) {
+                if shot_offset.is_none() {
+                    shot_offset = Some(ShotOffset::hit(&self.camera, hit_point, target.position));
+                    aimed_target = Some(target.position);
+                }
+                outcome.targets_hit += 1;
+                target.hp -= 1;
+                if target.hp < 1 {
+                    self.targets.old = Some(target.position);
+                    kills += 1;
+                }
+            }
+        }
+
+    /*
+        Misses are recorded relative to the target closest to the crosshair
+    */  if outcome.gun_shot && shot_offset.is_none() {
+            aimed_target = self.targets.nearest_to_aim(&self.camera).map(|t| t.position);
+            shot_offset = aimed_target.map(|pos| ShotOffset::miss(&self.camera, pos));
+        }
+
+    /*
+        Statistics, from the tick of the first hit on when the run starts on it
+    */  if run_started || outcome.targets_hit > 0 {
+            self.stats.advance(delta_time);
+            self.stats.mouse_path.record_frame(delta_time, self.camera.yaw, self.camera.pitch);
+            for _ in 0..kills {
+                self.stats.add_kill();
+            }
+            if let Some(offset) = shot_offset {
+                self.stats.add_offset(offset);
+            }
+            if outcome.gun_shot {
+                let target_dir = aimed_target.map(|pos| self.camera.position.vec_to(&pos).normalize());
+                self.stats.mouse_path.end_flick(target_dir);
+            }
+            if let Some(tracking) = &mut self.stats.tracking {
+                tracking.record_frame(delta_time, &self.camera, self.targets.nearest_to_aim(&self.camera), outcome.on_target);
+            }
+            if outcome.gun_shot && outcome.targets_hit > 0 {
+                self.stats.add_hit();
+            } else if outcome.gun_shot {
+                self.stats.add_shot();
+            }
+            self.stats.record_tick(delta_time, outcome.on_target);
+        }
+
+    /*
+        Remove Targets with hp <= 0 and spawn in a new one to keep the target count consistent
+    */  self.targets.vec.retain(|x| x.hp > 0);
+        if self.targets.vec.len() < scenario.t_settings.count {
+            self.targets.add_target();
+        }
+
+        if let Some(states) = &mut self.states {
+            let flags = if outcome.gun_shot { FLAG_SHOT } else { 0 } | if outcome.targets_hit > 0 { FLAG_HIT } else { 0 };
+            states.frames.push(FrameState::capture(now, &self.camera, &self.targets, flags));
+        }
+
+    /*
+        This should be done every frame to prevent single presses being detected as holding down
+    */  self.input.clear_just_pressed();
+        outcome
+    }
+}
@@ -0,0 +1,58 @@
+use crate::engine::CONFIG;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameState {
+/*
+    Lifecycle of a run in the window:
+
+    Countdown -> Playing -> Results -> Countdown (play again) | Exiting
+                    |  ^
+           Escape   v  |  Escape
+                  Paused -> Results (Enter) | Countdown (R)
+
+    Only Playing advances the simulation, so the timer and the targets stand still in every other state.
+    During the countdown the targets are already visible and the player can look around, but not shoot.
+    Paused remembers whether it interrupted the countdown, only then does resuming start the countdown again.
+*/
+    Countdown { remaining: f32 },
+    Playing,
+    Paused { in_countdown: bool },
+    Results,
+    Exiting,
+}
+
+impl GameState {
+    pub fn countdown() -> Self {
+        if CONFIG.run.countdown_secs > 0.0 {
+            GameState::Countdown { remaining: CONFIG.run.countdown_secs }
+        } else {
+            GameState::Playing
+        }
+    }
+
+    pub fn tick_countdown(self, delta_time: f32) -> Self {
+        match self {
+            GameState::Countdown { remaining } if remaining - delta_time <= 0.0 => GameState::Playing,
+            GameState::Countdown { remaining } => GameState::Countdown { remaining: remaining - delta_time },
+            other => other,
+        }
+    }
+
+    pub fn pause(self) -> Self {
+        GameState::Paused { in_countdown: matches!(self, GameState::Countdown { .. }) }
+    }
+
+    pub fn resume(self) -> Self {
+        // A run that has not started yet gets its full countdown again
+        match self {
+            GameState::Paused { in_countdown: true } => GameState::countdown(),
+            GameState::Paused { in_countdown: false } => GameState::Playing,
+            other => other,
+        }
+    }
+
+    pub fn captures_input(&self) -> bool {
+        // Mouse and movement only reach the simulation while the run is (about to be) played
+        matches!(self, GameState::Countdown { .. } | GameState::Playing)
+    }
+}
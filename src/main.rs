@@ -2,7 +2,82 @@ pub mod engine;
 
 fn main() -> Result<(), engine::EngineError>
 {
+    // Reported here instead of panicking wherever the settings are first used
+    engine::core::load_config(&engine::core::CONFIG_PATH)?;
     let colors = engine::color::Colors::detect_set_colors();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|a| a == "stats") {
+        return engine::cli::run_stats_mode(args.iter().any(|a| a == "--plain"), &colors);
+    }
+    if args.first().is_some_and(|a| a == "replay") {
+        match args.get(1) {
+            Some(path) => return engine::replay::verify_replay(std::path::Path::new(path)),
+            None => {
+                println!("Usage: Rust3dAimTrainer replay <replay file>");
+                return Ok(());
+            }
+        }
+    }
+    if args.first().is_some_and(|a| a == "view") {
+        match args.get(1) {
+            Some(path) => return engine::playback::run_viewer(std::path::Path::new(path), &engine::clock::MonotonicClock::new()),
+            None => {
+                println!("Usage: Rust3dAimTrainer view <replay file>");
+                return Ok(());
+            }
+        }
+    }
+
+    if args.first().is_some_and(|a| a == "headless") {
+        // headless <scenario> [script] [--seed N]
+        let mut seed = 0;
+        let mut positional: Vec<&str> = Vec::new();
+        let mut rest = args.iter().skip(1);
+        while let Some(arg) = rest.next() {
+            if arg == "--seed" {
+                // A typo must not silently turn into seed 0, CI would compare the wrong run
+                let value = rest.next().map_or("", |s| s.as_str());
+                seed = value.parse().map_err(|_| engine::EngineError::ArgErr("--seed", value.to_string()))?;
+            } else {
+                positional.push(arg);
+            }
+        }
+        match positional.first() {
+            Some(name) => return engine::headless::run_headless(name, positional.get(1).map(std::path::Path::new), seed),
+            None => {
+                println!("Usage: Rust3dAimTrainer headless <scenario> [input script] [--seed N]");
+                return Ok(());
+            }
+        }
+    }
+
+    if args.first().is_some_and(|a| a == "bot") {
+        // bot <scenario>... [--runs N] [--seed N]
+        let mut seed = 0;
+        let mut runs = None;
+        let mut names: Vec<&str> = Vec::new();
+        let mut rest = args.iter().skip(1);
+        while let Some(arg) = rest.next() {
+            match arg.as_str() {
+                "--seed" => {
+                    let value = rest.next().map_or("", |s| s.as_str());
+                    seed = value.parse().map_err(|_| engine::EngineError::ArgErr("--seed", value.to_string()))?;
+                }
+                "--runs" => {
+                    let value = rest.next().map_or("", |s| s.as_str());
+                    runs = Some(value.parse().map_err(|_| engine::EngineError::ArgErr("--runs", value.to_string()))?);
+                }
+                _ => names.push(arg),
+            }
+        }
+        if names.is_empty() {
+            println!("Usage: Rust3dAimTrainer bot <scenario>... [--runs N] [--seed N]");
+            return Ok(());
+        }
+        return engine::bot::run_bot(&names, runs, seed);
+    }
+
     engine::cli::print_logo(&colors);
     let mut scenarios = engine::scenario::get_scenarios(engine::cli::get_category(&colors), &colors);
 
@@ -10,6 +85,6 @@ fn main() -> Result<(), engine::EngineError>
         engine::rendering::cli::print_cli_select(i, &scenario.name, &colors);
     }
     let scenario_index = engine::cli::get_scenario_index(scenarios.len(), &colors);
-    engine::runtime::run(&mut scenarios[scenario_index])?;
+    engine::runtime::run(&mut scenarios[scenario_index], &engine::clock::MonotonicClock::new())?;
     Ok(())
 }